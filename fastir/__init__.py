@@ -1 +1 @@
-from .tooling import DataStream, extract_inline_features
\ No newline at end of file
+from .tooling import DataStream, extract_inline_features, extract_program_graph
\ No newline at end of file
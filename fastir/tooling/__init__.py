@@ -1,2 +1,3 @@
 from .data_stream import DataStream
-from .inline_features import extract_inline_features
\ No newline at end of file
+from .inline_features import extract_inline_features
+from .program_graph import extract_program_graph
\ No newline at end of file
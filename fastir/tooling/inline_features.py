@@ -1,20 +1,44 @@
 import io
 from typing import Optional
-from llvm_tools import extract_inline_features as llvm_extract_inline_features
+from llvm_tools import PassOptions, extract_inline_features as llvm_extract_inline_features
 import polars as pl
 
-def extract_inline_features(bc: bytes) -> Optional[pl.DataFrame]:
+def extract_inline_features(
+    bc: bytes,
+    include_terminators: bool = True,
+    opt_level: Optional[str] = None,
+    inline_thresholds: Optional[list[int]] = None,
+    feature_set: str = "default",
+    options: Optional[PassOptions] = None,
+) -> Optional[pl.DataFrame]:
     """Extract inline features from LLVM bitcode.
     
     Args:
         bc: LLVM bitcode bytes
+        include_terminators: Count block terminators in opcode histograms and
+            instruction counts. Pass False to reproduce the original counting.
+        opt_level: Label call sites with the inliner's decisions inside the full
+            pipeline for this level ("O0", "O1", "O2", "O3", "Os", "Oz") instead of
+            running the inliner on its own. Calls other passes remove are labelled
+            "deleted" in the inline_outcome column.
+        inline_thresholds: Rerun the inliner at each of these thresholds and add a
+            min_inline_threshold column with the lowest one that inlined the call.
+        feature_set: "default" for the llvm_tools columns, or "mlgo" for the features
+            of LLVM's ML inline advisor (MLInlineAdvisor) under their LLVM names,
+            InlineCost sub-features and cost_estimate included. Those are null for
+            calls LLVM's cost analysis finds can't be inlined.
+        options: PassOptions for the labelling runs, such as the inline_threshold,
+            inline_hint_threshold and hot/cold callsite thresholds of the inline cost
+            model. The sweep overrides inline_threshold.
         
     Returns:
         DataFrame with inline features or None if extraction fails
     """
     try:
         # llvm_tools.extract_inline_features returns a dataframe serialized to IPC (Arrow) bytes
-        df_bytes = llvm_extract_inline_features(bc)
+        df_bytes = llvm_extract_inline_features(
+            bc, include_terminators, opt_level, inline_thresholds, feature_set, options
+        )
         # Deserialize the IPC-formatted DataFrame
         return pl.read_ipc(io.BytesIO(df_bytes))
     except Exception as e:
@@ -0,0 +1,24 @@
+import io
+from typing import Optional
+from llvm_tools import ProgramGraph
+import polars as pl
+
+def extract_program_graph(bc: bytes) -> Optional[tuple[pl.DataFrame, pl.DataFrame]]:
+    """Build a ProGraML-style program graph from LLVM bitcode.
+    
+    Args:
+        bc: LLVM bitcode bytes
+        
+    Returns:
+        (nodes, edges) DataFrames or None if extraction fails. Use
+        llvm_tools.ProgramGraph directly for the edge_index numpy layout.
+    """
+    try:
+        graph = ProgramGraph(bc)
+        # both tables come serialized to IPC (Arrow) bytes
+        nodes = pl.read_ipc(io.BytesIO(graph.nodes()))
+        edges = pl.read_ipc(io.BytesIO(graph.edges()))
+        return nodes, edges
+    except Exception as e:
+        print(f"Error extracting program graph: {e}")
+        return None
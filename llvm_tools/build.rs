@@ -0,0 +1,26 @@
+use std::{env, path::PathBuf, process::Command};
+
+// Compiles src/llvm_shim.cpp against the LLVM llvm-sys links, found the way llvm-sys finds it
+fn main() {
+    println!("cargo:rerun-if-changed=src/llvm_shim.cpp");
+    println!("cargo:rerun-if-env-changed=LLVM_SYS_191_PREFIX");
+    let llvm_config = env::var("DEP_LLVM_19_CONFIG_PATH")
+        .map(PathBuf::from)
+        .or_else(|_| {
+            env::var("LLVM_SYS_191_PREFIX")
+                .map(|prefix| PathBuf::from(prefix).join("bin").join("llvm-config"))
+        })
+        .unwrap_or_else(|_| PathBuf::from("llvm-config"));
+    let output = Command::new(&llvm_config)
+        .arg("--cxxflags")
+        .output()
+        .unwrap_or_else(|e| panic!("could not run {}: {}", llvm_config.display(), e));
+    let cxxflags = String::from_utf8(output.stdout).expect("llvm-config prints UTF-8");
+
+    let mut build = cc::Build::new();
+    build.cpp(true).file("src/llvm_shim.cpp");
+    for flag in cxxflags.split_whitespace() {
+        build.flag(flag);
+    }
+    build.compile("llvm_shim");
+}
@@ -1,14 +1,147 @@
-def llvm_inline_pass(bc: bytes) -> bytes: ...
+from typing import Literal, overload
+
+import numpy as np
+import numpy.typing as npt
+
+@overload
+def llvm_inline_pass(bc: bytes, collect_remarks: Literal[False] = False) -> bytes: ...
+@overload
+def llvm_inline_pass(bc: bytes, collect_remarks: Literal[True]) -> tuple[bytes, list[InlineRemark]]: ...
+def run_passes(bc: bytes, pipeline: str, options: PassOptions | None = None) -> bytes: ...
 def bc_to_ir(bc: bytes) -> str: ...
-def extract_inline_features(bc: bytes) -> bytes: ...
+def autophase_feature_names() -> list[str]: ...
+def inst_count_feature_names() -> list[str]: ...
+def inst_count_norm_feature_names() -> list[str]: ...
+def extract_inline_features(
+    bc: bytes,
+    include_terminators: bool = True,
+    opt_level: Literal["O0", "O1", "O2", "O3", "Os", "Oz"] | None = None,
+    inline_thresholds: list[int] | None = None,
+    feature_set: Literal["default", "mlgo"] = "default",
+    options: PassOptions | None = None,
+) -> bytes: ...
+
+class PassOptions:
+    verify_each: bool | None
+    debug_logging: bool | None
+    loop_interleaving: bool | None
+    loop_vectorization: bool | None
+    slp_vectorization: bool | None
+    loop_unrolling: bool | None
+    forget_all_scev_in_loop_unroll: bool | None
+    licm_mssa_opt_cap: int | None
+    licm_mssa_no_acc_for_promotion_cap: int | None
+    call_graph_profile: bool | None
+    merge_functions: bool | None
+    inliner_threshold: int | None
+    inline_threshold: int | None
+    inline_hint_threshold: int | None
+    hot_callsite_threshold: int | None
+    locally_hot_callsite_threshold: int | None
+    cold_callsite_threshold: int | None
+
+    def __init__(
+        self,
+        verify_each: bool | None = None,
+        debug_logging: bool | None = None,
+        loop_interleaving: bool | None = None,
+        loop_vectorization: bool | None = None,
+        slp_vectorization: bool | None = None,
+        loop_unrolling: bool | None = None,
+        forget_all_scev_in_loop_unroll: bool | None = None,
+        licm_mssa_opt_cap: int | None = None,
+        licm_mssa_no_acc_for_promotion_cap: int | None = None,
+        call_graph_profile: bool | None = None,
+        merge_functions: bool | None = None,
+        inliner_threshold: int | None = None,
+        inline_threshold: int | None = None,
+        inline_hint_threshold: int | None = None,
+        hot_callsite_threshold: int | None = None,
+        locally_hot_callsite_threshold: int | None = None,
+        cold_callsite_threshold: int | None = None,
+    ) -> None: ...
+
+class InlineRemark:
+    kind: str
+    caller: str
+    callee: str
+    cost: int | None
+    threshold: int | None
+    reason: str | None
+    message: str
+
+class CallSite:
+    caller: str
+    bb_name: str
+    bb_index: int
+    instr_index: int
+    callee: str | None
+    kind: str
+    id: str
+    arg_count: int
+    const_arg_count: int
+    alloca_arg_count: int
+    global_arg_count: int
+    is_result_used: bool
+    is_in_entry_block: bool
+    block_position: float
+    loop_depth: int
+    is_in_cold_path: bool
+
+class LoopFeatures:
+    header: str
+    block_count: int
+    latch_count: int
+    exiting_block_count: int
+    exit_block_count: int
+    depth: int
+    trip_count: int | None
+
+class MemoryFeatures:
+    load_count: int
+    store_count: int
+    volatile_load_count: int
+    volatile_store_count: int
+    atomic_load_count: int
+    atomic_store_count: int
+    cmpxchg_count: int
+    atomicrmw_count: int
+    fence_count: int
+    orderings: dict[str, int]
+    alignments: dict[int, int]
+    gep_load_count: int
+    gep_store_count: int
+    gep_count: int
+    gep_index_count: int
+    gep_const_index_count: int
+    memcpy_count: int
+    memset_count: int
+    memmove_count: int
+
+    def gep_const_index_ratio(self) -> float: ...
+    def gep_access_ratio(self) -> float: ...
 
 class BBFeatures:
     name: str
     histogram: dict[str, int]
     opcode_entropy: float
+    result_types: dict[str, int]
+    operand_types: dict[str, int]
     function_calls: dict[str, int]
     call_count: int
+    call_sites: list[CallSite]
     instruction_count: int
+    successor_count: int
+    predecessor_count: int
+    terminator: str
+    switch_case_count: int
+    is_exit_block: bool
+    loop_depth: int
+    memory: MemoryFeatures
+    dom_tree_depth: int | None
+    postdom_tree_depth: int | None
+    dominated_block_count: int
+    is_always_executed: bool
 
     def mem_access_ratio(self) -> float: ...
 
@@ -17,17 +150,119 @@ class FnFeatures:
     bb_feats: dict[str, BBFeatures]
     arg_count: int
     instruction_count: int
+    histogram: dict[str, int]
+    opcode_entropy: float
+    result_types: dict[str, int]
+    operand_types: dict[str, int]
+    memory: MemoryFeatures
+    call_count: int
+    arith_count: int
+    memory_count: int
+    conversion_count: int
+    control_count: int
+    vector_count: int
+    atomic_count: int
+    mem_access_ratio: float
     has_var_args: bool
+    attributes: list[str]
+    return_attributes: list[str]
+    parameter_attributes: list[list[str]]
+    memory_effects: str
     has_inline_hint: bool
     bb_count: int
     has_always_inline: bool
     has_no_inline: bool
     is_recursive: bool
+    is_directly_recursive: bool
+    scc_id: int | None
+    scc_size: int
     outgoing_call_count: int
-    calls: set[tuple[str, str]]
+    is_declaration: bool
+    reachable_bb_count: int
+    conditionally_executed_block_count: int
+    edge_count: int
+    critical_edge_count: int
+    cyclomatic_complexity: int
+    exit_block_count: int
+    unreachable_bb_count: int
+    max_cfg_depth: int
+    back_edge_count: int
+    cfg: dict[str, list[str]]
+    is_available_externally: bool
+    linkage: str
+    has_local_linkage: bool
+    visibility: str
+    dll_storage_class: str
+    comdat: str | None
+    section: str | None
+    unnamed_addr: str | None
+    static_call_count: int
+    static_caller_count: int
+    address_taken_count: int
+    is_last_call_to_static: bool
+    immediate_dominators: dict[str, str]
+    immediate_post_dominators: dict[str, str]
+    dominance_frontiers: dict[str, list[str]]
+    loops: list[LoopFeatures]
+    loop_count: int
+    max_loop_depth: int
+    users: int
+    calls: list[CallSite]
+
+    def has_attribute(self, name: str) -> bool: ...
+    def has_parameter_attribute(self, name: str) -> bool: ...
+    def has_return_attribute(self, name: str) -> bool: ...
+    def autophase(self) -> list[int]: ...
+    def inst_count(self) -> list[int]: ...
+    def inst_count_norm(self) -> list[float]: ...
+
+class CallGraph:
+    nodes: list[str]
+    edges: list[tuple[str, str, int]]
+    sccs: list[list[str]]
+    post_order: list[str]
+
+    def in_degree(self, function: str) -> int: ...
+    def out_degree(self, function: str) -> int: ...
+    def to_dot(self) -> str: ...
+    def to_graphml(self) -> str: ...
 
 class ModFeatures:
     fn_feats: dict[str, FnFeatures]
-    call_sites: set[tuple[str, str, str]]
+    call_sites: list[CallSite]
+    call_graph: CallGraph
     
+    def __init__(
+        self, bc: bytes, include_terminators: bool = True, memory_effects: bool = True
+    ) -> None: ...
+    def autophase(self) -> list[int]: ...
+    def inst_count(self) -> list[int]: ...
+    def inst_count_norm(self) -> list[float]: ...
+
+class Ir2Vec:
+    dimension: int
+
+    def __init__(self, vocab_path: str) -> None: ...
+    def instruction_embeddings(
+        self, mod_features: ModFeatures, function: str, flow_aware: bool = False
+    ) -> dict[str, list[list[float]]]: ...
+    def bb_embeddings(
+        self, mod_features: ModFeatures, function: str, flow_aware: bool = False
+    ) -> dict[str, list[float]]: ...
+    def fn_embedding(
+        self, mod_features: ModFeatures, function: str, flow_aware: bool = False
+    ) -> list[float]: ...
+    def fn_embeddings(
+        self, mod_features: ModFeatures, flow_aware: bool = False
+    ) -> dict[str, list[float]]: ...
+
+class ProgramGraph:
+    node_count: int
+    edge_count: int
+
     def __init__(self, bc: bytes) -> None: ...
+    def nodes(self) -> bytes: ...
+    def edges(self) -> bytes: ...
+    def edge_index(
+        self, flow: Literal["control", "data", "call"] | None = None
+    ) -> npt.NDArray[np.int64]: ...
@@ -0,0 +1,318 @@
+use std::fmt::{self, Debug, Display, Formatter};
+
+use llvm_ir::function::{FunctionAttribute, ParameterAttribute};
+
+/// The function attributes given a boolean column per callee and caller in the inline
+/// dataframe, `callee_attr_<name>` and `caller_attr_<name>`. The last four hold when the
+/// `memory(...)` attribute is within what they allowed before LLVM 16 replaced them.
+pub const FUNCTION_ATTRIBUTE_COLUMNS: [&str; 14] = [
+    "inlinehint",
+    "optsize",
+    "minsize",
+    "optnone",
+    "cold",
+    "hot",
+    "noreturn",
+    "nounwind",
+    "norecurse",
+    "willreturn",
+    "readnone",
+    "readonly",
+    "writeonly",
+    "argmemonly",
+];
+
+/// The parameter attributes given a column telling whether any parameter of the callee
+/// or caller carries them, `callee_param_<name>` and `caller_param_<name>`
+pub const PARAMETER_ATTRIBUTE_COLUMNS: [&str; 5] =
+    ["noalias", "nonnull", "byval", "sret", "readonly"];
+
+/// The return attributes given a column, `callee_ret_<name>` and `caller_ret_<name>`
+pub const RETURN_ATTRIBUTE_COLUMNS: [&str; 3] = ["noalias", "nonnull", "noundef"];
+
+/// The names of function attributes as LLVM spells them, sorted and without repeats.
+/// String attributes go by their key, such as `target-cpu`. `memory(...)` is left to
+/// `MemoryEffects`, llvm-ir does not decode it.
+pub fn function_attribute_names(attributes: &[FunctionAttribute]) -> Vec<String> {
+    attribute_names(attributes.iter().map(|attribute| match attribute {
+        FunctionAttribute::StringAttribute { kind, .. } => Some(kind.clone()),
+        _ => function_attribute_name(attribute).map(str::to_string),
+    }))
+}
+
+/// The names of parameter or return attributes as LLVM spells them, sorted and without
+/// repeats
+pub fn parameter_attribute_names(attributes: &[ParameterAttribute]) -> Vec<String> {
+    attribute_names(attributes.iter().map(|attribute| match attribute {
+        ParameterAttribute::StringAttribute { kind, .. } => Some(kind.clone()),
+        _ => parameter_attribute_name(attribute).map(str::to_string),
+    }))
+}
+
+fn attribute_names(names: impl Iterator<Item = Option<String>>) -> Vec<String> {
+    let mut names: Vec<String> = names.flatten().collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+// the LLVM name of a function attribute, `None` for those llvm-ir does not decode
+fn function_attribute_name(attribute: &FunctionAttribute) -> Option<&'static str> {
+    let name = match attribute {
+        FunctionAttribute::AlwaysInline => "alwaysinline",
+        FunctionAttribute::Builtin => "builtin",
+        FunctionAttribute::Cold => "cold",
+        FunctionAttribute::Convergent => "convergent",
+        FunctionAttribute::Hot => "hot",
+        FunctionAttribute::InlineHint => "inlinehint",
+        FunctionAttribute::JumpTable => "jumptable",
+        FunctionAttribute::MinimizeSize => "minsize",
+        FunctionAttribute::MustProgress => "mustprogress",
+        FunctionAttribute::Naked => "naked",
+        FunctionAttribute::NoBuiltin => "nobuiltin",
+        FunctionAttribute::NoDuplicate => "noduplicate",
+        FunctionAttribute::NoFree => "nofree",
+        FunctionAttribute::NoImplicitFloat => "noimplicitfloat",
+        FunctionAttribute::NoInline => "noinline",
+        FunctionAttribute::NonLazyBind => "nonlazybind",
+        FunctionAttribute::NoRedZone => "noredzone",
+        FunctionAttribute::NoRecurse => "norecurse",
+        FunctionAttribute::NoReturn => "noreturn",
+        FunctionAttribute::NoSync => "nosync",
+        FunctionAttribute::NoUnwind => "nounwind",
+        FunctionAttribute::NullPointerIsValid => "null_pointer_is_valid",
+        FunctionAttribute::OptForFuzzing => "optforfuzzing",
+        FunctionAttribute::OptNone => "optnone",
+        FunctionAttribute::OptSize => "optsize",
+        FunctionAttribute::ReturnsTwice => "returns_twice",
+        FunctionAttribute::SafeStack => "safestack",
+        FunctionAttribute::SanitizeAddress => "sanitize_address",
+        FunctionAttribute::SanitizeHWAddress => "sanitize_hwaddress",
+        FunctionAttribute::SanitizeMemory => "sanitize_memory",
+        FunctionAttribute::SanitizeThread => "sanitize_thread",
+        FunctionAttribute::ShadowCallStack => "shadowcallstack",
+        FunctionAttribute::Speculatable => "speculatable",
+        FunctionAttribute::SpeculativeLoadHardening => "speculative_load_hardening",
+        FunctionAttribute::StackProtect => "ssp",
+        FunctionAttribute::StackProtectReq => "sspreq",
+        FunctionAttribute::StackProtectStrong => "sspstrong",
+        FunctionAttribute::StrictFP => "strictfp",
+        FunctionAttribute::WillReturn => "willreturn",
+        _ => return None,
+    };
+    Some(name)
+}
+
+// the LLVM name of a parameter or return attribute, `None` for those llvm-ir does not
+// decode
+fn parameter_attribute_name(attribute: &ParameterAttribute) -> Option<&'static str> {
+    let name = match attribute {
+        ParameterAttribute::ZeroExt => "zeroext",
+        ParameterAttribute::SignExt => "signext",
+        ParameterAttribute::InReg => "inreg",
+        ParameterAttribute::ByVal(..) => "byval",
+        ParameterAttribute::SRet(..) => "sret",
+        ParameterAttribute::Alignment(_) => "align",
+        ParameterAttribute::NoAlias => "noalias",
+        ParameterAttribute::NoCapture => "nocapture",
+        ParameterAttribute::NoFree => "nofree",
+        ParameterAttribute::Nest => "nest",
+        ParameterAttribute::Returned => "returned",
+        ParameterAttribute::NonNull => "nonnull",
+        ParameterAttribute::Dereferenceable(_) => "dereferenceable",
+        ParameterAttribute::DereferenceableOrNull(_) => "dereferenceable_or_null",
+        ParameterAttribute::SwiftSelf => "swiftself",
+        ParameterAttribute::SwiftError => "swifterror",
+        ParameterAttribute::ImmArg => "immarg",
+        ParameterAttribute::NoUndef => "noundef",
+        ParameterAttribute::ReadNone => "readnone",
+        ParameterAttribute::ReadOnly => "readonly",
+        ParameterAttribute::WriteOnly => "writeonly",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// How a function may access one kind of memory, LLVM's `ModRefInfo`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModRef {
+    None = 0,
+    Read = 1,
+    Write = 2,
+    ReadWrite = 3,
+}
+
+impl ModRef {
+    // LLVM's two bits, the low one for reads and the high one for writes
+    fn from_bits(bits: u64) -> Self {
+        match bits & 3 {
+            0 => ModRef::None,
+            1 => ModRef::Read,
+            2 => ModRef::Write,
+            _ => ModRef::ReadWrite,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModRef::None => "none",
+            ModRef::Read => "read",
+            ModRef::Write => "write",
+            ModRef::ReadWrite => "readwrite",
+        }
+    }
+
+    pub fn reads(&self) -> bool {
+        matches!(self, ModRef::Read | ModRef::ReadWrite)
+    }
+
+    pub fn writes(&self) -> bool {
+        matches!(self, ModRef::Write | ModRef::ReadWrite)
+    }
+}
+
+/// A function's `memory(...)` attribute: how it may access the memory its pointer
+/// arguments point to, memory no IR can reach, and every other memory
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryEffects {
+    pub argmem: ModRef,
+    pub inaccessiblemem: ModRef,
+    pub other: ModRef,
+}
+
+impl MemoryEffects {
+    /// Any access to any memory, the effects of a function without the attribute
+    pub const UNKNOWN: Self = Self {
+        argmem: ModRef::ReadWrite,
+        inaccessiblemem: ModRef::ReadWrite,
+        other: ModRef::ReadWrite,
+    };
+
+    /// Decodes the attribute's integer value, two bits per location in LLVM's
+    /// `IRMemLocation` order
+    pub fn from_int(value: u64) -> Self {
+        Self {
+            argmem: ModRef::from_bits(value),
+            inaccessiblemem: ModRef::from_bits(value >> 2),
+            other: ModRef::from_bits(value >> 4),
+        }
+    }
+
+    /// Whether the effects stay within those of an attribute `memory(...)` replaced in
+    /// LLVM 16: `readnone`, `readonly`, `writeonly`, `argmemonly`, `inaccessiblememonly`
+    /// or `inaccessiblemem_or_argmemonly`
+    pub fn implies(&self, attribute: &str) -> bool {
+        let locations = [self.argmem, self.inaccessiblemem, self.other];
+        match attribute {
+            "readnone" => locations.iter().all(|location| *location == ModRef::None),
+            "readonly" => !locations.iter().any(ModRef::writes),
+            "writeonly" => !locations.iter().any(ModRef::reads),
+            "argmemonly" => self.inaccessiblemem == ModRef::None && self.other == ModRef::None,
+            "inaccessiblememonly" => self.argmem == ModRef::None && self.other == ModRef::None,
+            "inaccessiblemem_or_argmemonly" => self.other == ModRef::None,
+            _ => false,
+        }
+    }
+}
+
+// as LLVM prints it: the access to other memory as the default, then every location
+// accessed differently
+impl Display for MemoryEffects {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let any =
+            ModRef::from_bits(self.argmem as u64 | self.inaccessiblemem as u64 | self.other as u64);
+        let mut parts = Vec::new();
+        if self.other != ModRef::None || any == self.other {
+            parts.push(self.other.as_str().to_string());
+        }
+        for (location, access) in [
+            ("argmem", self.argmem),
+            ("inaccessiblemem", self.inaccessiblemem),
+        ] {
+            if access != self.other {
+                parts.push(format!("{}: {}", location, access.as_str()));
+            }
+        }
+        write!(f, "memory({})", parts.join(", "))
+    }
+}
+
+/// The lowercased variant name of an llvm-ir enum value, or its LLVM spelling from
+/// `spellings` when the two differ
+pub fn llvm_spelling(value: &impl Debug, spellings: &[(&str, &str)]) -> String {
+    let variant = format!("{:?}", value)
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    spellings
+        .iter()
+        .find(|(name, _)| *name == variant)
+        .map(|(_, spelling)| spelling.to_string())
+        .unwrap_or(variant)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::module;
+
+    #[test]
+    fn names_attributes_as_llvm_spells_them() {
+        let module = module(
+            r#"
+define void @f(ptr noalias nonnull %p, ptr byval(i32) %q) #0 {
+  ret void
+}
+
+attributes #0 = { minsize noinline nounwind optsize "target-cpu"="generic" }
+"#,
+        );
+        let function = &module.functions[0];
+        assert_eq!(
+            function_attribute_names(&function.function_attributes),
+            ["minsize", "noinline", "nounwind", "optsize", "target-cpu"]
+        );
+        assert_eq!(
+            parameter_attribute_names(&function.parameters[0].attributes),
+            ["noalias", "nonnull"]
+        );
+        assert_eq!(
+            parameter_attribute_names(&function.parameters[1].attributes),
+            ["byval"]
+        );
+    }
+
+    #[test]
+    fn prints_memory_effects_as_llvm_does() {
+        let print = |value: u64| MemoryEffects::from_int(value).to_string();
+        assert_eq!(print(0), "memory(none)");
+        assert_eq!(print(0b01), "memory(argmem: read)");
+        assert_eq!(print(0b10_00), "memory(inaccessiblemem: write)");
+        assert_eq!(
+            print(0b01_00_11),
+            "memory(read, argmem: readwrite, inaccessiblemem: none)"
+        );
+        assert_eq!(print(0b01_01_01), "memory(read)");
+        assert_eq!(MemoryEffects::UNKNOWN.to_string(), "memory(readwrite)");
+    }
+
+    #[test]
+    fn memory_effects_imply_the_attributes_they_replaced() {
+        let argmem_read = MemoryEffects::from_int(0b01);
+        assert!(argmem_read.implies("readonly"));
+        assert!(argmem_read.implies("argmemonly"));
+        assert!(argmem_read.implies("inaccessiblemem_or_argmemonly"));
+        assert!(!argmem_read.implies("readnone"));
+        assert!(!argmem_read.implies("writeonly"));
+        assert!(!argmem_read.implies("inaccessiblememonly"));
+
+        let none = MemoryEffects::from_int(0);
+        for attribute in ["readnone", "readonly", "writeonly", "argmemonly"] {
+            assert!(none.implies(attribute));
+        }
+        for attribute in ["readnone", "readonly", "writeonly", "argmemonly", "cold"] {
+            assert!(!MemoryEffects::UNKNOWN.implies(attribute));
+        }
+    }
+}
@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+
+use llvm_ir::{Constant, Function, Instruction, Operand, Terminator, Type};
+use pyo3::pyfunction;
+
+use crate::{
+    bb_features::{instruction_to_string, terminator_targets, terminator_to_string},
+    operands::{instruction_operands, terminator_operands},
+};
+
+/// The Autophase features (Huang et al., 2019) in the order of CompilerGym's observation
+pub const AUTOPHASE_FEATURE_NAMES: [&str; 56] = [
+    "BBNumArgsHi",
+    "BBNumArgsLo",
+    "onePred",
+    "onePredOneSuc",
+    "onePredTwoSuc",
+    "oneSuccessor",
+    "twoPred",
+    "twoPredOneSuc",
+    "twoEach",
+    "twoSuccessor",
+    "morePreds",
+    "BB03Phi",
+    "BBHiPhi",
+    "BBNoPhi",
+    "BeginPhi",
+    "BranchCount",
+    "returnInt",
+    "CriticalCount",
+    "NumEdges",
+    "const32Bit",
+    "const64Bit",
+    "numConstZeroes",
+    "numConstOnes",
+    "UncondBranches",
+    "binaryConstArg",
+    "NumAShrInst",
+    "NumAddInst",
+    "NumAllocaInst",
+    "NumAndInst",
+    "BlockMid",
+    "BlockLow",
+    "NumBitCastInst",
+    "NumBrInst",
+    "NumCallInst",
+    "NumGetElementPtrInst",
+    "NumICmpInst",
+    "NumLShrInst",
+    "NumLoadInst",
+    "NumMulInst",
+    "NumOrInst",
+    "NumPHIInst",
+    "NumRetInst",
+    "NumSExtInst",
+    "NumSelectInst",
+    "NumShlInst",
+    "NumStoreInst",
+    "NumSubInst",
+    "NumTruncInst",
+    "NumXorInst",
+    "NumZExtInst",
+    "TotalBlocks",
+    "TotalInsts",
+    "TotalMemInst",
+    "TotalFuncs",
+    "ArgsPhi",
+    "testUnary",
+];
+
+// opcode -> the Num*Inst feature counting it
+const OPCODE_FEATURES: [(&str, &str); 23] = [
+    ("ashr", "NumAShrInst"),
+    ("add", "NumAddInst"),
+    ("alloca", "NumAllocaInst"),
+    ("and", "NumAndInst"),
+    ("bitcast", "NumBitCastInst"),
+    ("br", "NumBrInst"),
+    ("call", "NumCallInst"),
+    ("getelementptr", "NumGetElementPtrInst"),
+    ("icmp", "NumICmpInst"),
+    ("lshr", "NumLShrInst"),
+    ("load", "NumLoadInst"),
+    ("mul", "NumMulInst"),
+    ("or", "NumOrInst"),
+    ("phi", "NumPHIInst"),
+    ("ret", "NumRetInst"),
+    ("sext", "NumSExtInst"),
+    ("select", "NumSelectInst"),
+    ("shl", "NumShlInst"),
+    ("store", "NumStoreInst"),
+    ("sub", "NumSubInst"),
+    ("trunc", "NumTruncInst"),
+    ("xor", "NumXorInst"),
+    ("zext", "NumZExtInst"),
+];
+
+// the opcodes CompilerGym's Autophase counts in TotalMemInst, calls included
+const MEMORY_OPCODES: [&str; 6] = ["alloca", "load", "store", "getelementptr", "call", "invoke"];
+
+const BINARY_OPCODES: [&str; 18] = [
+    "add", "sub", "mul", "udiv", "sdiv", "urem", "srem", "shl", "lshr", "ashr", "and", "or", "xor",
+    "fadd", "fsub", "fmul", "fdiv", "frem",
+];
+
+// the subclasses of LLVM's UnaryInstruction
+const UNARY_OPCODES: [&str; 19] = [
+    "alloca",
+    "load",
+    "vaarg",
+    "extractvalue",
+    "fneg",
+    "freeze",
+    "trunc",
+    "zext",
+    "sext",
+    "fptrunc",
+    "fpext",
+    "fptoui",
+    "fptosi",
+    "uitofp",
+    "sitofp",
+    "ptrtoint",
+    "inttoptr",
+    "bitcast",
+    "addrspacecast",
+];
+
+/// The names of the Autophase features, in the order `autophase()` returns them
+#[pyfunction]
+pub fn autophase_feature_names() -> Vec<&'static str> {
+    AUTOPHASE_FEATURE_NAMES.to_vec()
+}
+
+/// Computes the Autophase feature vector of a function definition. Edges and
+/// predecessors are counted per terminator operand, so a `condbr` with both labels
+/// naming the same block adds two edges, as in LLVM.
+pub fn autophase(function: &Function) -> Vec<u64> {
+    let mut counts: HashMap<&'static str, u64> = HashMap::new();
+    let mut count = |feature: &'static str, n: u64| *counts.entry(feature).or_insert(0) += n;
+
+    let positions: HashMap<String, usize> = function
+        .basic_blocks
+        .iter()
+        .enumerate()
+        .map(|(position, bb)| (bb.name.to_string(), position))
+        .collect();
+    let targets: Vec<Vec<usize>> = function
+        .basic_blocks
+        .iter()
+        .map(|bb| {
+            terminator_targets(&bb.term)
+                .into_iter()
+                .filter_map(|target| positions.get(&target.to_string()).copied())
+                .collect()
+        })
+        .collect();
+    let mut pred_counts = vec![0; function.basic_blocks.len()];
+    for &target in targets.iter().flatten() {
+        pred_counts[target] += 1;
+    }
+
+    count("TotalFuncs", 1);
+    for (bb_index, bb) in function.basic_blocks.iter().enumerate() {
+        count("TotalBlocks", 1);
+
+        let succ_count = targets[bb_index].len();
+        let pred_count = pred_counts[bb_index];
+        count("NumEdges", succ_count as u64);
+        if succ_count > 1 {
+            // an edge out of a branching block into a merging one
+            let critical = targets[bb_index]
+                .iter()
+                .filter(|&&target| pred_counts[target] > 1)
+                .count();
+            count("CriticalCount", critical as u64);
+        }
+        match succ_count {
+            1 => count("oneSuccessor", 1),
+            2 => count("twoSuccessor", 1),
+            _ => {}
+        }
+        match pred_count {
+            0 => {}
+            1 => count("onePred", 1),
+            2 => count("twoPred", 1),
+            _ => count("morePreds", 1),
+        }
+        match (pred_count, succ_count) {
+            (1, 1) => count("onePredOneSuc", 1),
+            (1, 2) => count("onePredTwoSuc", 1),
+            (2, 1) => count("twoPredOneSuc", 1),
+            (2, 2) => count("twoEach", 1),
+            _ => {}
+        }
+
+        let phis: Vec<_> = bb
+            .instrs
+            .iter()
+            .map_while(|instr| match instr {
+                Instruction::Phi(phi) => Some(phi),
+                _ => None,
+            })
+            .collect();
+        let phi_args = phis
+            .iter()
+            .map(|phi| phi.incoming_values.len() as u64)
+            .sum::<u64>();
+        count("BeginPhi", phis.len() as u64);
+        count("ArgsPhi", phi_args);
+        match phi_args {
+            0 => {}
+            1..=5 => count("BBNumArgsLo", 1),
+            _ => count("BBNumArgsHi", 1),
+        }
+        match phis.len() {
+            0 => count("BBNoPhi", 1),
+            1..=3 => count("BB03Phi", 1),
+            _ => count("BBHiPhi", 1),
+        }
+
+        match bb.instrs.len() + 1 {
+            0..=14 => count("BlockLow", 1),
+            15..=500 => count("BlockMid", 1),
+            _ => {}
+        }
+
+        for instr in bb.instrs.iter() {
+            let opcode = instruction_to_string(instr);
+            let operands = instruction_operands(instr);
+            count("TotalInsts", 1);
+            if MEMORY_OPCODES.contains(&opcode) {
+                count("TotalMemInst", 1);
+            }
+            if UNARY_OPCODES.contains(&opcode) {
+                count("testUnary", 1);
+            }
+            if BINARY_OPCODES.contains(&opcode)
+                && operands
+                    .iter()
+                    .any(|operand| matches!(operand, Operand::ConstantOperand(_)))
+            {
+                count("binaryConstArg", 1);
+            }
+            if let Instruction::Call(call) = instr {
+                if let Type::FuncType { result_type, .. } = call.function_ty.as_ref() {
+                    if matches!(result_type.as_ref(), Type::IntegerType { .. }) {
+                        count("returnInt", 1);
+                    }
+                }
+            }
+            if let Some((_, feature)) = OPCODE_FEATURES.iter().find(|(op, _)| *op == opcode) {
+                count(*feature, 1);
+            }
+            for operand in operands {
+                count_constant(operand, &mut count);
+            }
+        }
+
+        let opcode = terminator_to_string(&bb.term);
+        count("TotalInsts", 1);
+        if MEMORY_OPCODES.contains(&opcode) {
+            count("TotalMemInst", 1);
+        }
+        if opcode == "br" {
+            count("BranchCount", 1);
+            if matches!(bb.term, Terminator::Br(_)) {
+                count("UncondBranches", 1);
+            }
+        }
+        if let Some((_, feature)) = OPCODE_FEATURES.iter().find(|(op, _)| *op == opcode) {
+            count(*feature, 1);
+        }
+        for operand in terminator_operands(&bb.term) {
+            count_constant(operand, &mut count);
+        }
+    }
+
+    AUTOPHASE_FEATURE_NAMES
+        .iter()
+        .map(|name| counts.get(name).copied().unwrap_or(0))
+        .collect()
+}
+
+// the integer constant features of one operand
+fn count_constant(operand: &Operand, count: &mut impl FnMut(&'static str, u64)) {
+    let Operand::ConstantOperand(constant) = operand else {
+        return;
+    };
+    if let Constant::Int { bits, value } = constant.as_ref() {
+        match bits {
+            32 => count("const32Bit", 1),
+            64 => count("const64Bit", 1),
+            _ => {}
+        }
+        match value {
+            0 => count("numConstZeroes", 1),
+            1 => count("numConstOnes", 1),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mod_features::ModFeatures, test_utils::bitcode};
+
+    const IR: &str = r#"
+declare i32 @ext(i32)
+
+define i32 @f(i32 %n) {
+entry:
+  %p = alloca i32
+  store i32 0, ptr %p
+  %c = icmp sgt i32 %n, 1
+  br i1 %c, label %then, label %join
+then:
+  %v = load i32, ptr %p
+  %r = call i32 @ext(i32 %v)
+  br label %join
+join:
+  %x = phi i32 [ 1, %entry ], [ %r, %then ]
+  ret i32 %x
+}
+"#;
+
+    #[test]
+    fn matches_the_hand_counted_vector() {
+        let mod_features = ModFeatures::from_bc(&bitcode(IR), true).unwrap();
+        #[rustfmt::skip]
+        let expected = [
+            // BBNumArgsHi, BBNumArgsLo, onePred, onePredOneSuc, onePredTwoSuc, oneSuccessor
+            0, 1, 1, 1, 0, 1,
+            // twoPred, twoPredOneSuc, twoEach, twoSuccessor, morePreds
+            1, 0, 0, 1, 0,
+            // BB03Phi, BBHiPhi, BBNoPhi, BeginPhi, BranchCount, returnInt, CriticalCount
+            1, 0, 2, 1, 2, 1, 1,
+            // NumEdges, const32Bit, const64Bit, numConstZeroes, numConstOnes
+            3, 4, 0, 1, 3,
+            // UncondBranches, binaryConstArg, AShr, Add, Alloca, And, BlockMid, BlockLow
+            1, 0, 0, 0, 1, 0, 0, 3,
+            // BitCast, Br, Call, GetElementPtr, ICmp, LShr, Load, Mul, Or, PHI, Ret
+            0, 2, 1, 0, 1, 0, 1, 0, 0, 1, 1,
+            // SExt, Select, Shl, Store, Sub, Trunc, Xor, ZExt
+            0, 0, 0, 1, 0, 0, 0, 0,
+            // TotalBlocks, TotalInsts, TotalMemInst, TotalFuncs, ArgsPhi, testUnary
+            3, 9, 4, 1, 2, 2,
+        ];
+        assert_eq!(mod_features.autophase(), expected);
+    }
+
+    #[test]
+    fn declarations_are_not_counted() {
+        let mod_features = ModFeatures::from_bc(&bitcode(IR), true).unwrap();
+        let features = mod_features.fn_feats["ext"].autophase();
+        assert_eq!(features.len(), AUTOPHASE_FEATURE_NAMES.len());
+        assert!(features.iter().all(|&count| count == 0));
+    }
+
+    #[test]
+    fn counts_invokes_as_memory_instructions() {
+        let ir = r#"
+declare void @g()
+declare i32 @__gxx_personality_v0(...)
+
+define void @f() personality ptr @__gxx_personality_v0 {
+entry:
+  invoke void @g() to label %done unwind label %lpad
+done:
+  ret void
+lpad:
+  %lp = landingpad { ptr, i32 } cleanup
+  resume { ptr, i32 } %lp
+}
+"#;
+        let module = crate::test_utils::module(ir);
+        let features = autophase(module.get_func_by_name("f").unwrap());
+        let total_mem_inst = AUTOPHASE_FEATURE_NAMES
+            .iter()
+            .position(|&name| name == "TotalMemInst")
+            .unwrap();
+        assert_eq!(features[total_mem_inst], 1);
+    }
+}
@@ -1,10 +1,14 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-// TODO: implement a function to calculate how many branches this block can branch to
-
-use llvm_ir::{BasicBlock, Instruction, Operand, Constant};
+use llvm_ir::{types::Types, BasicBlock, Instruction, Name, Terminator};
 use pyo3::{pyclass, pymethods, PyResult};
 
+use crate::{
+    call_site::{block_call_sites, CallContext, CallSite},
+    memory_features::MemoryFeatures,
+    type_features::type_histograms,
+};
+
 #[pyclass]
 #[derive(Clone)]
 pub struct BBFeatures {
@@ -17,28 +21,117 @@ pub struct BBFeatures {
     #[pyo3(get)]
     pub opcode_entropy: f64,
     #[pyo3(get)]
+    /// How many instruction results have each type, named as in LLVM (`i32`, `double`,
+    /// `<4 x float>`, `ptr addrspace(1)`) with every struct as `struct` and every array
+    /// as `array`
+    pub result_types: HashMap<String, usize>,
+    #[pyo3(get)]
+    /// How many instruction operands have each type, named as in `result_types`
+    pub operand_types: HashMap<String, usize>,
+    #[pyo3(get)]
     pub function_calls: HashMap<String, usize>,
     #[pyo3(get)]
     pub call_count: usize,
     #[pyo3(get)]
+    pub call_sites: Vec<CallSite>,
+    #[pyo3(get)]
     pub instruction_count: usize,
+    #[pyo3(get)]
+    /// The number of distinct blocks this block can branch to
+    pub successor_count: usize,
+    #[pyo3(get)]
+    /// The number of distinct blocks branching to this block, filled in by `FnFeatures`
+    pub predecessor_count: usize,
+    #[pyo3(get)]
+    /// The kind of terminator ending this block (br, condbr, switch, ret, ...)
+    pub terminator: String,
+    #[pyo3(get)]
+    /// The number of non-default cases if the block ends in a switch
+    pub switch_case_count: usize,
+    #[pyo3(get)]
+    /// Whether control leaves the function from this block (no successors)
+    pub is_exit_block: bool,
+    #[pyo3(get)]
+    /// The number of natural loops containing this block, filled in by `FnFeatures`
+    pub loop_depth: usize,
+    #[pyo3(get)]
+    /// Details of the block's loads, stores, atomics, `getelementptr`s and memory
+    /// intrinsics
+    pub memory: MemoryFeatures,
+    #[pyo3(get)]
+    /// The depth of the block in the dominator tree, the entry block at 0, `None` when it
+    /// is unreachable; filled in by `FnFeatures`
+    pub dom_tree_depth: Option<usize>,
+    #[pyo3(get)]
+    /// The depth of the block in the post-dominator tree, blocks leaving the function at 0,
+    /// `None` when it never leaves the function; filled in by `FnFeatures`
+    pub postdom_tree_depth: Option<usize>,
+    #[pyo3(get)]
+    /// The number of blocks this block strictly dominates, filled in by `FnFeatures`
+    pub dominated_block_count: usize,
+    #[pyo3(get)]
+    /// Whether the block post-dominates the entry block, so runs on every call; filled
+    /// in by `FnFeatures`
+    pub is_always_executed: bool,
+    /// The names of the distinct successor blocks
+    pub successors: Vec<String>,
 }
 
 impl BBFeatures {
-    pub fn new(basic_block: &BasicBlock) -> Self {
+    /// `include_terminators` controls whether the block's terminator is counted in the
+    /// histogram, type counts and instruction count; passing `false` reproduces the
+    /// original counting. `gep_results` are the function's `getelementptr` results, see
+    /// `memory_features::gep_results`.
+    pub fn new(
+        function_name: &str,
+        bb_index: usize,
+        basic_block: &BasicBlock,
+        types: &Types,
+        call_context: &CallContext,
+        gep_results: &HashSet<&Name>,
+        include_terminators: bool,
+    ) -> Self {
         let name = basic_block.name.to_string();
-        let histogram = bb_histogram(basic_block);
+        let histogram = bb_histogram(basic_block, include_terminators);
         let opcode_entropy = opcode_entropy(&histogram);
-        let (function_calls, call_count) = function_calls(basic_block);
-        let instruction_count = basic_block.instrs.len();
+        let (result_types, operand_types) =
+            type_histograms(basic_block, types, include_terminators);
+        let call_sites = block_call_sites(function_name, bb_index, basic_block, call_context);
+        let function_calls = function_calls(&call_sites);
+        let call_count = call_sites.len();
+        let instruction_count = basic_block.instrs.len() + include_terminators as usize;
+        let successors = terminator_successors(&basic_block.term);
+        let successor_count = successors.len();
+        let terminator = terminator_kind(&basic_block.term).to_string();
+        let switch_case_count = match &basic_block.term {
+            Terminator::Switch(switch) => switch.dests.len(),
+            _ => 0,
+        };
+        let is_exit_block = successors.is_empty();
+        let memory = MemoryFeatures::new(basic_block, gep_results);
         Self {
             _basic_block: basic_block.clone(),
             name,
             histogram,
             opcode_entropy,
+            result_types,
+            operand_types,
             function_calls,
             call_count,
+            call_sites,
             instruction_count,
+            successor_count,
+            predecessor_count: 0,
+            terminator,
+            switch_case_count,
+            is_exit_block,
+            loop_depth: 0,
+            memory,
+            dom_tree_depth: None,
+            postdom_tree_depth: None,
+            dominated_block_count: 0,
+            is_always_executed: false,
+            successors,
         }
     }
 }
@@ -52,7 +145,7 @@ impl BBFeatures {
     }
 }
 
-fn instruction_to_string(instruction: &Instruction) -> &'static str {
+pub fn instruction_to_string(instruction: &Instruction) -> &'static str {
     match instruction {
         // Integer binary ops
         Instruction::Add(_) => "add",
@@ -126,18 +219,84 @@ fn instruction_to_string(instruction: &Instruction) -> &'static str {
     }
 }
 
-fn bb_histogram(bb: &BasicBlock) -> HashMap<String, usize> {
+/// The opcode of a terminator as LLVM spells it, conditional branches as `br`
+pub fn terminator_to_string(terminator: &Terminator) -> &'static str {
+    match terminator_kind(terminator) {
+        "condbr" => "br",
+        kind => kind,
+    }
+}
+
+fn terminator_kind(terminator: &Terminator) -> &'static str {
+    match terminator {
+        Terminator::Ret(_) => "ret",
+        Terminator::Br(_) => "br",
+        Terminator::CondBr(_) => "condbr",
+        Terminator::Switch(_) => "switch",
+        Terminator::IndirectBr(_) => "indirectbr",
+        Terminator::Invoke(_) => "invoke",
+        Terminator::Resume(_) => "resume",
+        Terminator::Unreachable(_) => "unreachable",
+        Terminator::CleanupRet(_) => "cleanupret",
+        Terminator::CatchRet(_) => "catchret",
+        Terminator::CatchSwitch(_) => "catchswitch",
+        Terminator::CallBr(_) => "callbr",
+    }
+}
+
+/// Every block label a terminator can branch to, in order and with repeats
+pub fn terminator_targets(terminator: &Terminator) -> Vec<&Name> {
+    match terminator {
+        Terminator::Br(br) => vec![&br.dest],
+        Terminator::CondBr(condbr) => vec![&condbr.true_dest, &condbr.false_dest],
+        Terminator::Switch(switch) => std::iter::once(&switch.default_dest)
+            .chain(switch.dests.iter().map(|(_, dest)| dest))
+            .collect(),
+        Terminator::IndirectBr(indirectbr) => indirectbr.possible_dests.iter().collect(),
+        Terminator::Invoke(invoke) => vec![&invoke.return_label, &invoke.exception_label],
+        Terminator::CleanupRet(cleanupret) => cleanupret.unwind_dest.iter().collect(),
+        Terminator::CatchRet(catchret) => vec![&catchret.successor],
+        Terminator::CatchSwitch(catchswitch) => catchswitch
+            .catch_handlers
+            .iter()
+            .chain(catchswitch.default_unwind_dest.iter())
+            .collect(),
+        Terminator::CallBr(callbr) => std::iter::once(&callbr.return_label)
+            .chain(callbr.other_labels.iter())
+            .collect(),
+        Terminator::Ret(_) | Terminator::Resume(_) | Terminator::Unreachable(_) => vec![],
+    }
+}
+
+/// Distinct successor block names in the order they appear in the terminator
+pub fn terminator_successors(terminator: &Terminator) -> Vec<String> {
+    let dests = terminator_targets(terminator);
+    let mut successors: Vec<String> = Vec::with_capacity(dests.len());
+    for dest in dests {
+        let dest = dest.to_string();
+        if !successors.contains(&dest) {
+            successors.push(dest);
+        }
+    }
+    successors
+}
+
+fn bb_histogram(bb: &BasicBlock, include_terminator: bool) -> HashMap<String, usize> {
     let mut histogram = HashMap::new();
     for instr in bb.instrs.iter() {
         let instr_str = instruction_to_string(instr).to_string();
         *histogram.entry(instr_str).or_insert(0) += 1;
     }
+    if include_terminator {
+        let term_str = terminator_to_string(&bb.term).to_string();
+        *histogram.entry(term_str).or_insert(0) += 1;
+    }
     histogram
 }
 
 // shannon entropy of opcode distribution
 // H = -sum(p_i * log2(p_i))
-fn opcode_entropy(histogram: &HashMap<String, usize>) -> f64 {
+pub fn opcode_entropy(histogram: &HashMap<String, usize>) -> f64 {
     let total = histogram.values().sum::<usize>() as f64;
     let inv_total = 1.0 / total; // Avoid repeated division
 
@@ -150,34 +309,131 @@ fn opcode_entropy(histogram: &HashMap<String, usize>) -> f64 {
         .sum()
 }
 
-fn function_calls(bb: &BasicBlock) -> (HashMap<String, usize>, usize) {
+fn function_calls(call_sites: &[CallSite]) -> HashMap<String, usize> {
     let mut calls = HashMap::new();
-    let mut call_count = 0;
-    for instr in bb.instrs.iter() {
-        match instr {
-            Instruction::Call(call) => {
-                call_count += 1;
-                if let Some(operand) = call.function.as_ref().right() {
-                    let function_name = match operand {
-                        // Direct function call via global reference
-                        Operand::ConstantOperand(constant_ref) => {
-                            match constant_ref.as_ref() {
-                                llvm_ir::Constant::GlobalReference { name, .. } => {
-                                    Some(name.to_string())
-                                }
-                                _ => None,
-                            }
-                        }
-                        _ => None,
-                    };
-                    
-                    if let Some(name) = function_name {
-                        *calls.entry(name).or_insert(0) += 1;
-                    }
-                }
-            }
-            _ => {}
-        }
+    for callee in call_sites.iter().filter_map(|site| site.callee.as_ref()) {
+        *calls.entry(callee.clone()).or_insert(0) += 1;
+    }
+    calls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fn_features::FnFeatures, memory_features::gep_results, test_utils::module};
+
+    const IR: &str = r#"
+define i32 @f(i32 %x) {
+entry:
+  switch i32 %x, label %default [
+    i32 0, label %zero
+    i32 1, label %one
+    i32 2, label %one
+  ]
+zero:
+  br label %exit
+one:
+  %c = icmp eq i32 %x, 1
+  br i1 %c, label %exit, label %exit
+default:
+  unreachable
+exit:
+  ret i32 %x
+}
+"#;
+
+    fn features() -> FnFeatures {
+        let module = module(IR);
+        FnFeatures::from_def(&module.functions[0], &module.types, true)
+    }
+
+    #[test]
+    fn counts_distinct_successors_and_predecessors() {
+        let features = features();
+        let bb = |name: &str| &features.bb_feats[name];
+        assert_eq!(bb("%entry").successor_count, 3);
+        assert_eq!(bb("%entry").successors, ["%default", "%zero", "%one"]);
+        assert_eq!(bb("%one").successor_count, 1);
+        assert_eq!(bb("%exit").predecessor_count, 2);
+        assert_eq!(bb("%one").predecessor_count, 1);
+        assert_eq!(bb("%entry").predecessor_count, 0);
+    }
+
+    #[test]
+    fn describes_terminators() {
+        let features = features();
+        let bb = |name: &str| &features.bb_feats[name];
+        assert_eq!(bb("%entry").terminator, "switch");
+        assert_eq!(bb("%entry").switch_case_count, 3);
+        assert_eq!(bb("%zero").terminator, "br");
+        assert_eq!(bb("%one").terminator, "condbr");
+        assert_eq!(bb("%one").switch_case_count, 0);
+        assert_eq!(bb("%default").terminator, "unreachable");
+        assert_eq!(bb("%exit").terminator, "ret");
+    }
+
+    #[test]
+    fn exit_blocks_have_no_successors() {
+        let features = features();
+        let exits: Vec<bool> = ["%entry", "%zero", "%one", "%default", "%exit"]
+            .iter()
+            .map(|name| features.bb_feats[*name].is_exit_block)
+            .collect();
+        assert_eq!(exits, [false, false, false, true, true]);
+    }
+
+    #[test]
+    fn counts_terminators_on_request() {
+        let module = module(IR);
+        let with = FnFeatures::from_def(&module.functions[0], &module.types, true);
+        let without = FnFeatures::from_def(&module.functions[0], &module.types, false);
+        let one = (&with.bb_feats["%one"], &without.bb_feats["%one"]);
+        assert_eq!(one.0.histogram.get("br"), Some(&1));
+        assert_eq!(one.1.histogram.get("br"), None);
+        assert_eq!(one.0.histogram.get("condbr"), None);
+        assert_eq!(one.0.instruction_count, 2);
+        assert_eq!(one.1.instruction_count, 1);
+        assert_eq!(with.histogram.get("switch"), Some(&1));
+        assert_eq!(with.instruction_count, 6);
+        assert_eq!(without.instruction_count, 1);
+    }
+
+    #[test]
+    fn counts_memory_through_geps_of_other_blocks() {
+        let module = module(
+            r#"
+define i32 @g(ptr %p) {
+entry:
+  %q = getelementptr i32, ptr %p, i64 1
+  store i32 0, ptr %p, align 4
+  br label %use
+use:
+  %x = load i32, ptr %q, align 4
+  store i32 %x, ptr %q, align 4
+  ret i32 %x
+}
+"#,
+        );
+        let function = &module.functions[0];
+        let bb = BBFeatures::new(
+            "g",
+            1,
+            &function.basic_blocks[1],
+            &module.types,
+            &CallContext::new(function),
+            &gep_results(function),
+            true,
+        );
+        assert_eq!(bb.memory.load_count, 1);
+        assert_eq!(bb.memory.gep_load_count, 1);
+        assert_eq!(bb.memory.gep_store_count, 1);
+        assert_eq!(bb.memory.gep_count, 0);
+
+        // the function's counts are the sum of its blocks'
+        let features = FnFeatures::from_def(function, &module.types, true);
+        assert_eq!(features.bb_feats["%use"].memory.gep_load_count, 1);
+        assert_eq!(features.bb_feats["%entry"].memory.store_count, 1);
+        assert_eq!(features.memory.store_count, 2);
+        assert_eq!(features.memory.gep_store_count, 1);
     }
-    (calls, call_count)
 }
@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+
+use pyo3::{prelude::*, pyclass, pymethods, PyResult};
+
+use crate::fn_features::FnFeatures;
+
+#[pyclass]
+#[derive(Clone)]
+/// The direct call graph between the functions defined in a module. Calls to
+/// declarations, intrinsics and through pointers are not edges, as in the call graph
+/// the CGSCC inliner walks.
+pub struct CallGraph {
+    /// The defined functions, in module order
+    pub functions: Vec<String>,
+    /// The distinct defined functions each function calls directly
+    pub callees: Vec<Vec<usize>>,
+    /// The number of call sites behind each edge in `callees`
+    pub call_counts: Vec<Vec<usize>>,
+    /// The strongly connected components, every callee's before its callers'
+    pub sccs: Vec<Vec<usize>>,
+    positions: HashMap<String, usize>,
+}
+
+#[pymethods]
+impl CallGraph {
+    /// The defined functions, in module order
+    #[getter]
+    pub fn nodes(&self) -> Vec<String> {
+        self.functions.clone()
+    }
+
+    /// Every `(caller, callee, call_count)` edge, in caller and first call order
+    #[getter]
+    pub fn edges(&self) -> Vec<(String, String, usize)> {
+        self.edge_list()
+            .map(|(caller, callee, count)| {
+                (
+                    self.functions[caller].clone(),
+                    self.functions[callee].clone(),
+                    count,
+                )
+            })
+            .collect()
+    }
+
+    /// The strongly connected components bottom-up, callees before their callers
+    #[getter]
+    pub fn sccs(&self) -> Vec<Vec<String>> {
+        self.sccs
+            .iter()
+            .map(|scc| scc.iter().map(|&f| self.functions[f].clone()).collect())
+            .collect()
+    }
+
+    /// The functions bottom-up, SCC by SCC, the order the CGSCC inliner visits them in
+    #[getter]
+    pub fn post_order(&self) -> Vec<String> {
+        self.sccs
+            .iter()
+            .flatten()
+            .map(|&f| self.functions[f].clone())
+            .collect()
+    }
+
+    /// The number of distinct defined functions calling `function`
+    pub fn in_degree(&self, function: &str) -> PyResult<usize> {
+        let position = self.require(function)?;
+        Ok(self
+            .callees
+            .iter()
+            .filter(|callees| callees.contains(&position))
+            .count())
+    }
+
+    /// The number of distinct defined functions `function` calls
+    pub fn out_degree(&self, function: &str) -> PyResult<usize> {
+        Ok(self.callees[self.require(function)?].len())
+    }
+
+    /// The graph in Graphviz DOT, edges labelled and weighted with their call count
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph call_graph {\n");
+        for function in self.functions.iter() {
+            dot.push_str(&format!("  {};\n", dot_id(function)));
+        }
+        for (caller, callee, count) in self.edge_list() {
+            dot.push_str(&format!(
+                "  {} -> {} [label={}, weight={}];\n",
+                dot_id(&self.functions[caller]),
+                dot_id(&self.functions[callee]),
+                count,
+                count
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The graph in GraphML, with each node's SCC and each edge's call count
+    pub fn to_graphml(&self) -> String {
+        let mut scc_ids = vec![0; self.functions.len()];
+        for (scc_id, scc) in self.sccs.iter().enumerate() {
+            for &function in scc {
+                scc_ids[function] = scc_id;
+            }
+        }
+
+        let mut graphml = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"scc\" for=\"node\" attr.name=\"scc\" attr.type=\"int\"/>\n",
+            "  <key id=\"calls\" for=\"edge\" attr.name=\"calls\" attr.type=\"int\"/>\n",
+            "  <graph id=\"call_graph\" edgedefault=\"directed\">\n",
+        ));
+        for (function, scc_id) in self.functions.iter().zip(scc_ids) {
+            graphml.push_str(&format!(
+                "    <node id=\"{}\"><data key=\"scc\">{}</data></node>\n",
+                xml_escape(function),
+                scc_id
+            ));
+        }
+        for (caller, callee, count) in self.edge_list() {
+            graphml.push_str(&format!(
+                "    <edge source=\"{}\" target=\"{}\"><data key=\"calls\">{}</data></edge>\n",
+                xml_escape(&self.functions[caller]),
+                xml_escape(&self.functions[callee]),
+                count
+            ));
+        }
+        graphml.push_str("  </graph>\n</graphml>\n");
+        graphml
+    }
+}
+
+impl CallGraph {
+    pub fn new<'a>(functions: impl IntoIterator<Item = &'a FnFeatures>) -> Self {
+        let functions: Vec<&FnFeatures> = functions.into_iter().collect();
+        let positions: HashMap<String, usize> = functions
+            .iter()
+            .enumerate()
+            .map(|(position, function)| (function.name.clone(), position))
+            .collect();
+        let mut callees: Vec<Vec<usize>> = Vec::with_capacity(functions.len());
+        let mut call_counts: Vec<Vec<usize>> = Vec::with_capacity(functions.len());
+        for function in functions.iter() {
+            let mut function_callees: Vec<usize> = Vec::new();
+            let mut function_call_counts: Vec<usize> = Vec::new();
+            for callee in function
+                .calls
+                .iter()
+                .filter_map(|site| site.callee.as_ref())
+                .filter_map(|callee| positions.get(callee).copied())
+            {
+                match function_callees.iter().position(|&known| known == callee) {
+                    Some(edge) => function_call_counts[edge] += 1,
+                    None => {
+                        function_callees.push(callee);
+                        function_call_counts.push(1);
+                    }
+                }
+            }
+            callees.push(function_callees);
+            call_counts.push(function_call_counts);
+        }
+        let sccs = strongly_connected_components(&callees);
+        Self {
+            functions: functions
+                .iter()
+                .map(|function| function.name.clone())
+                .collect(),
+            callees,
+            call_counts,
+            sccs,
+            positions,
+        }
+    }
+
+    // (caller, callee, call count) of every edge
+    fn edge_list(&self) -> impl Iterator<Item = (usize, usize, usize)> + '_ {
+        self.callees
+            .iter()
+            .zip(self.call_counts.iter())
+            .enumerate()
+            .flat_map(|(caller, (callees, counts))| {
+                callees
+                    .iter()
+                    .zip(counts.iter())
+                    .map(move |(&callee, &count)| (caller, callee, count))
+            })
+    }
+
+    fn require(&self, function: &str) -> PyResult<usize> {
+        self.position(function).ok_or_else(|| {
+            PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                "function {} is not defined in the module",
+                function
+            ))
+        })
+    }
+
+    /// The position of a defined function in `functions`
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.positions.get(name).copied()
+    }
+
+    /// The height of every function: 0 if it calls no function outside of its SCC, one
+    /// more than its highest callee in another SCC otherwise, shared by the whole SCC.
+    /// This is the `callsite_height` of LLVM's ML inline advisor.
+    pub fn heights(&self) -> Vec<u64> {
+        let mut heights: Vec<Option<u64>> = vec![None; self.functions.len()];
+        for scc in self.sccs.iter() {
+            // callees in other SCCs already have a height, those in this one don't
+            let height = scc
+                .iter()
+                .flat_map(|&function| self.callees[function].iter())
+                .filter_map(|&callee| heights[callee])
+                .map(|height| height + 1)
+                .max()
+                .unwrap_or(0);
+            for &function in scc {
+                heights[function] = Some(height);
+            }
+        }
+        heights
+            .into_iter()
+            .map(|height| height.unwrap_or(0))
+            .collect()
+    }
+}
+
+// Tarjan's algorithm, iterative so deep call chains can't overflow the stack. Components
+// come out in reverse topological order with their members in module order.
+fn strongly_connected_components(succs: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut index = vec![usize::MAX; succs.len()];
+    let mut lowlink = vec![0; succs.len()];
+    let mut on_stack = vec![false; succs.len()];
+    let mut stack = Vec::new();
+    let mut sccs = Vec::new();
+    let mut next_index = 0;
+
+    for root in 0..succs.len() {
+        if index[root] != usize::MAX {
+            continue;
+        }
+        index[root] = next_index;
+        lowlink[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        // (node, index of the next successor to visit)
+        let mut work = vec![(root, 0)];
+        while let Some((node, next)) = work.last_mut() {
+            let node = *node;
+            if let Some(&succ) = succs[node].get(*next) {
+                *next += 1;
+                if index[succ] == usize::MAX {
+                    index[succ] = next_index;
+                    lowlink[succ] = next_index;
+                    next_index += 1;
+                    stack.push(succ);
+                    on_stack[succ] = true;
+                    work.push((succ, 0));
+                } else if on_stack[succ] {
+                    lowlink[node] = lowlink[node].min(index[succ]);
+                }
+                continue;
+            }
+
+            work.pop();
+            if let Some(&(parent, _)) = work.last() {
+                lowlink[parent] = lowlink[parent].min(lowlink[node]);
+            }
+            if lowlink[node] == index[node] {
+                let mut scc = Vec::new();
+                while let Some(member) = stack.pop() {
+                    on_stack[member] = false;
+                    scc.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                scc.sort_unstable();
+                sccs.push(scc);
+            }
+        }
+    }
+    sccs
+}
+
+// a quoted DOT identifier
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mod_features::ModFeatures, test_utils::bitcode};
+
+    const IR: &str = r#"
+define void @a() {
+entry:
+  call void @b()
+  call void @b()
+  call void @ext()
+  ret void
+}
+
+define void @b() {
+entry:
+  call void @c()
+  ret void
+}
+
+define void @c() {
+entry:
+  call void @b()
+  ret void
+}
+
+declare void @ext()
+"#;
+
+    fn call_graph() -> CallGraph {
+        ModFeatures::from_bc(&bitcode(IR), true).unwrap().call_graph
+    }
+
+    #[test]
+    fn links_defined_functions_with_call_counts() {
+        let call_graph = call_graph();
+        assert_eq!(call_graph.nodes(), ["a", "b", "c"]);
+        let edge =
+            |caller: &str, callee: &str, count| (caller.to_string(), callee.to_string(), count);
+        assert_eq!(
+            call_graph.edges(),
+            [edge("a", "b", 2), edge("b", "c", 1), edge("c", "b", 1)]
+        );
+        assert_eq!(call_graph.position("ext"), None);
+        assert_eq!(call_graph.in_degree("b").unwrap(), 2);
+        assert_eq!(call_graph.out_degree("a").unwrap(), 1);
+    }
+
+    #[test]
+    fn orders_sccs_bottom_up() {
+        let call_graph = call_graph();
+        assert_eq!(call_graph.sccs(), [vec!["b", "c"], vec!["a"]]);
+        assert_eq!(call_graph.post_order(), ["b", "c", "a"]);
+        assert_eq!(call_graph.heights(), [1, 0, 0]);
+    }
+
+    #[test]
+    fn exports_dot_and_graphml() {
+        let call_graph = call_graph();
+        assert_eq!(
+            call_graph.to_dot(),
+            concat!(
+                "digraph call_graph {\n",
+                "  \"a\";\n",
+                "  \"b\";\n",
+                "  \"c\";\n",
+                "  \"a\" -> \"b\" [label=2, weight=2];\n",
+                "  \"b\" -> \"c\" [label=1, weight=1];\n",
+                "  \"c\" -> \"b\" [label=1, weight=1];\n",
+                "}\n",
+            )
+        );
+        let graphml = call_graph.to_graphml();
+        assert!(graphml.contains("<node id=\"a\"><data key=\"scc\">1</data></node>"));
+        assert!(
+            graphml.contains("<edge source=\"a\" target=\"b\"><data key=\"calls\">2</data></edge>")
+        );
+    }
+
+    #[test]
+    fn escapes_names() {
+        assert_eq!(dot_id("a\"b\\c"), "\"a\\\"b\\\\c\"");
+        assert_eq!(xml_escape("<a&'b'>"), "&lt;a&amp;&apos;b&apos;&gt;");
+    }
+}
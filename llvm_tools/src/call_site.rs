@@ -0,0 +1,427 @@
+use std::collections::HashSet;
+
+use llvm_ir::{
+    function::ParameterAttribute, BasicBlock, Constant, Function, Instruction, Name, Operand,
+    Terminator,
+};
+use pyo3::{pyclass, pymethods};
+
+use crate::operands::{instruction_operands, terminator_operands};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CallKind {
+    /// `call` to a known function
+    Direct,
+    /// `call` through a function pointer
+    Indirect,
+    /// `invoke` terminator, direct or indirect
+    Invoke,
+    /// `callbr` terminator
+    CallBr,
+    /// `call` to an `llvm.*` intrinsic
+    Intrinsic,
+    /// `call` of an inline assembly blob
+    InlineAsm,
+}
+
+impl CallKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CallKind::Direct => "direct",
+            CallKind::Indirect => "indirect",
+            CallKind::Invoke => "invoke",
+            CallKind::CallBr => "callbr",
+            CallKind::Intrinsic => "intrinsic",
+            CallKind::InlineAsm => "inline_asm",
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Clone)]
+/// A call-like instruction (call, invoke or callbr)
+pub struct CallSite {
+    #[pyo3(get)]
+    /// The name of the calling function
+    pub caller: String,
+    #[pyo3(get)]
+    /// The name of the basic block containing the call
+    pub bb_name: String,
+    #[pyo3(get)]
+    /// The position of the block in the function
+    pub bb_index: usize,
+    #[pyo3(get)]
+    /// The position of the call in its block, the terminator comes last
+    pub instr_index: usize,
+    #[pyo3(get)]
+    /// The name of the called function, `None` for indirect calls and inline asm
+    pub callee: Option<String>,
+    pub kind: CallKind,
+    #[pyo3(get)]
+    /// The number of arguments passed
+    pub arg_count: usize,
+    #[pyo3(get)]
+    /// The number of constant arguments, references to globals included
+    pub const_arg_count: usize,
+    #[pyo3(get)]
+    /// The number of arguments that are the result of an alloca in the caller
+    pub alloca_arg_count: usize,
+    #[pyo3(get)]
+    /// The number of arguments that are references to globals
+    pub global_arg_count: usize,
+    #[pyo3(get)]
+    /// Whether the value returned by the call is used
+    pub is_result_used: bool,
+    #[pyo3(get)]
+    /// Whether the call is in the caller's entry block
+    pub is_in_entry_block: bool,
+    #[pyo3(get)]
+    /// The position of the call in its block, from 0 for the first instruction to 1 for
+    /// the terminator
+    pub block_position: f64,
+    #[pyo3(get)]
+    /// The number of loops containing the call, filled in by `FnFeatures`
+    pub loop_depth: usize,
+    #[pyo3(get)]
+    /// Whether no `ret` is reachable from the call, so that every path from it ends in
+    /// `unreachable`, `resume` or never leaves the function; filled in by `FnFeatures`
+    pub is_in_cold_path: bool,
+}
+
+#[pymethods]
+impl CallSite {
+    #[getter]
+    pub fn kind(&self) -> &'static str {
+        self.kind.as_str()
+    }
+
+    /// A module-unique identifier of the form `caller:bb_index:instr_index`
+    #[getter]
+    pub fn id(&self) -> String {
+        format!("{}:{}:{}", self.caller, self.bb_index, self.instr_index)
+    }
+}
+
+/// Function-wide facts needed to describe the arguments and result of a call
+pub struct CallContext<'a> {
+    /// The results of the function's allocas
+    allocas: HashSet<&'a Name>,
+    /// Every local value used as an operand in the function
+    used_values: HashSet<&'a Name>,
+}
+
+impl<'a> CallContext<'a> {
+    pub fn new(function: &'a Function) -> Self {
+        let mut allocas = HashSet::new();
+        let mut used_values = HashSet::new();
+        for bb in function.basic_blocks.iter() {
+            for instr in bb.instrs.iter() {
+                if let Instruction::Alloca(alloca) = instr {
+                    allocas.insert(&alloca.dest);
+                }
+                used_values.extend(
+                    instruction_operands(instr)
+                        .into_iter()
+                        .filter_map(local_name),
+                );
+            }
+            used_values.extend(
+                terminator_operands(&bb.term)
+                    .into_iter()
+                    .filter_map(local_name),
+            );
+        }
+        Self {
+            allocas,
+            used_values,
+        }
+    }
+}
+
+/// Collects every call-like instruction of a basic block, terminator included
+pub fn block_call_sites(
+    caller: &str,
+    bb_index: usize,
+    bb: &BasicBlock,
+    context: &CallContext,
+) -> Vec<CallSite> {
+    let bb_name = bb.name.to_string();
+    let site = |instr_index: usize,
+                callee: Option<String>,
+                kind: CallKind,
+                arguments: &[(Operand, Vec<ParameterAttribute>)],
+                result: Option<&Name>| {
+        let mut const_arg_count = 0;
+        let mut alloca_arg_count = 0;
+        let mut global_arg_count = 0;
+        for (arg, _) in arguments {
+            match arg {
+                Operand::ConstantOperand(constant) => {
+                    const_arg_count += 1;
+                    if matches!(constant.as_ref(), Constant::GlobalReference { .. }) {
+                        global_arg_count += 1;
+                    }
+                }
+                Operand::LocalOperand { name, .. } if context.allocas.contains(name) => {
+                    alloca_arg_count += 1;
+                }
+                _ => {}
+            }
+        }
+        CallSite {
+            caller: caller.to_string(),
+            bb_name: bb_name.clone(),
+            bb_index,
+            instr_index,
+            callee,
+            kind,
+            arg_count: arguments.len(),
+            const_arg_count,
+            alloca_arg_count,
+            global_arg_count,
+            is_result_used: result.is_some_and(|result| context.used_values.contains(result)),
+            is_in_entry_block: bb_index == 0,
+            block_position: instr_index as f64 / bb.instrs.len().max(1) as f64,
+            loop_depth: 0,
+            is_in_cold_path: false,
+        }
+    };
+
+    let mut sites: Vec<CallSite> = bb
+        .instrs
+        .iter()
+        .enumerate()
+        .filter_map(|(instr_index, instr)| match instr {
+            Instruction::Call(call) => {
+                let result = call.dest.as_ref();
+                if call.function.is_left() {
+                    return Some(site(
+                        instr_index,
+                        None,
+                        CallKind::InlineAsm,
+                        &call.arguments,
+                        result,
+                    ));
+                }
+                let callee = callee_name(call.function.as_ref().right());
+                let kind = match &callee {
+                    Some(name) if name.starts_with("llvm.") => CallKind::Intrinsic,
+                    Some(_) => CallKind::Direct,
+                    None => CallKind::Indirect,
+                };
+                Some(site(instr_index, callee, kind, &call.arguments, result))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let term_index = bb.instrs.len();
+    match &bb.term {
+        Terminator::Invoke(invoke) => sites.push(site(
+            term_index,
+            callee_name(invoke.function.as_ref().right()),
+            CallKind::Invoke,
+            &invoke.arguments,
+            Some(&invoke.result),
+        )),
+        Terminator::CallBr(callbr) => sites.push(site(
+            term_index,
+            callee_name(callbr.function.as_ref().right()),
+            CallKind::CallBr,
+            &callbr.arguments,
+            Some(&callbr.result),
+        )),
+        _ => {}
+    }
+    sites
+}
+
+/// The name of a directly called function, `None` if the call goes through a pointer
+pub fn callee_name(function: Option<&Operand>) -> Option<String> {
+    match function? {
+        // Direct function call via global reference
+        Operand::ConstantOperand(constant_ref) => match constant_ref.as_ref() {
+            Constant::GlobalReference { name, .. } => Some(global_name(name)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The bare name of a global, without the `%` sigil `Name`'s `Display` adds
+pub fn global_name(name: &Name) -> String {
+    match name {
+        Name::Name(name) => name.to_string(),
+        Name::Number(number) => number.to_string(),
+    }
+}
+
+fn local_name(operand: &Operand) -> Option<&Name> {
+    match operand {
+        Operand::LocalOperand { name, .. } => Some(name),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{fn_features::FnFeatures, test_utils::module};
+
+    const IR: &str = r#"
+declare void @g(i32)
+declare i32 @__gxx_personality_v0(...)
+declare void @llvm.donothing()
+
+define void @f(ptr %fp) personality ptr @__gxx_personality_v0 {
+entry:
+  call void @g(i32 1)
+  call void %fp(i32 2)
+  call void @llvm.donothing()
+  call void asm sideeffect "nop", ""()
+  invoke void @g(i32 3) to label %cont unwind label %lpad
+cont:
+  callbr void asm "", "!i"() to label %done [label %lpad.other]
+lpad.other:
+  ret void
+done:
+  ret void
+lpad:
+  %lp = landingpad { ptr, i32 } cleanup
+  resume { ptr, i32 } %lp
+}
+"#;
+
+    fn calls() -> Vec<super::CallSite> {
+        let module = module(IR);
+        let function = module.get_func_by_name("f").unwrap();
+        FnFeatures::from_def(function, &module.types, true).calls
+    }
+
+    #[test]
+    fn classifies_call_kinds() {
+        let kinds: Vec<&str> = calls().iter().map(|site| site.kind()).collect();
+        assert_eq!(
+            kinds,
+            [
+                "direct",
+                "indirect",
+                "intrinsic",
+                "inline_asm",
+                "invoke",
+                "callbr"
+            ]
+        );
+    }
+
+    #[test]
+    fn names_callees_of_direct_calls_only() {
+        let callees: Vec<Option<String>> = calls().into_iter().map(|site| site.callee).collect();
+        assert_eq!(
+            callees,
+            [
+                Some("g".to_string()),
+                None,
+                Some("llvm.donothing".to_string()),
+                None,
+                Some("g".to_string()),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn terminator_calls_come_last_in_their_block() {
+        let calls = calls();
+        assert_eq!((calls[4].bb_index, calls[4].instr_index), (0, 4));
+        assert_eq!((calls[5].bb_index, calls[5].instr_index), (1, 0));
+        assert_eq!(calls[4].block_position, 1.0);
+    }
+
+    #[test]
+    fn repeated_calls_get_distinct_ids() {
+        let ids: Vec<String> = calls().iter().map(|site| site.id()).collect();
+        assert_eq!(ids, ["f:0:0", "f:0:1", "f:0:2", "f:0:3", "f:0:4", "f:1:0"]);
+        let module = module(IR);
+        let function = module.get_func_by_name("f").unwrap();
+        let features = FnFeatures::from_def(function, &module.types, true);
+        assert_eq!(features.bb_feats["%entry"].function_calls["g"], 2);
+    }
+
+    const CONTEXT_IR: &str = r#"
+@g = global i32 0
+declare i32 @h(ptr, i32, ptr)
+declare void @abort() noreturn
+
+define void @ctx(i1 %c) {
+entry:
+  %slot = alloca i32
+  %r = call i32 @h(ptr %slot, i32 7, ptr @g)
+  store i32 %r, ptr %slot
+  br label %outer
+outer:
+  br label %inner
+inner:
+  %unused = call i32 @h(ptr %slot, i32 %r, ptr %slot)
+  br i1 %c, label %inner, label %latch
+latch:
+  br i1 %c, label %outer, label %exit
+exit:
+  br i1 %c, label %done, label %fail
+fail:
+  call void @abort()
+  unreachable
+done:
+  ret void
+}
+"#;
+
+    fn context_calls() -> Vec<super::CallSite> {
+        let module = module(CONTEXT_IR);
+        let function = module.get_func_by_name("ctx").unwrap();
+        FnFeatures::from_def(function, &module.types, true).calls
+    }
+
+    #[test]
+    fn describes_call_arguments() {
+        let calls = context_calls();
+        let args = |site: &super::CallSite| {
+            (
+                site.arg_count,
+                site.const_arg_count,
+                site.alloca_arg_count,
+                site.global_arg_count,
+            )
+        };
+        assert_eq!(args(&calls[0]), (3, 2, 1, 1));
+        assert_eq!(args(&calls[1]), (3, 0, 2, 0));
+        assert_eq!(args(&calls[2]), (0, 0, 0, 0));
+    }
+
+    #[test]
+    fn tells_whether_results_are_used() {
+        let used: Vec<bool> = context_calls()
+            .iter()
+            .map(|site| site.is_result_used)
+            .collect();
+        assert_eq!(used, [true, false, false]);
+    }
+
+    #[test]
+    fn places_calls_in_loops_and_cold_paths() {
+        let calls = context_calls();
+        let context: Vec<(bool, usize, bool)> = calls
+            .iter()
+            .map(|site| {
+                (
+                    site.is_in_entry_block,
+                    site.loop_depth,
+                    site.is_in_cold_path,
+                )
+            })
+            .collect();
+        assert_eq!(
+            context,
+            [(true, 0, false), (false, 2, false), (false, 0, true)]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::{HashMap, VecDeque};
+
+use llvm_ir::Function;
+
+use crate::bb_features::terminator_successors;
+
+/// The control flow graph of a function over block positions, the entry block is 0
+pub struct Cfg {
+    /// The name of every block, by position
+    pub names: Vec<String>,
+    /// The distinct successors of every block
+    pub succs: Vec<Vec<usize>>,
+    /// The distinct predecessors of every block
+    pub preds: Vec<Vec<usize>>,
+}
+
+impl Cfg {
+    pub fn new(function: &Function) -> Self {
+        let names: Vec<String> = function
+            .basic_blocks
+            .iter()
+            .map(|bb| bb.name.to_string())
+            .collect();
+        let positions: HashMap<&str, usize> = names
+            .iter()
+            .enumerate()
+            .map(|(position, name)| (name.as_str(), position))
+            .collect();
+        let succs: Vec<Vec<usize>> = function
+            .basic_blocks
+            .iter()
+            .map(|bb| {
+                terminator_successors(&bb.term)
+                    .iter()
+                    .filter_map(|succ| positions.get(succ.as_str()).copied())
+                    .collect()
+            })
+            .collect();
+        let mut preds = vec![Vec::new(); names.len()];
+        for (bb, bb_succs) in succs.iter().enumerate() {
+            for &succ in bb_succs {
+                preds[succ].push(bb);
+            }
+        }
+        Self {
+            names,
+            succs,
+            preds,
+        }
+    }
+
+    pub fn block_count(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Whether each block can reach one of `targets`, targets included
+    pub fn can_reach(&self, targets: impl IntoIterator<Item = usize>) -> Vec<bool> {
+        let mut reaches = vec![false; self.block_count()];
+        let mut stack: Vec<usize> = targets.into_iter().collect();
+        while let Some(bb) = stack.pop() {
+            if !reaches[bb] {
+                reaches[bb] = true;
+                stack.extend(self.preds[bb].iter().copied());
+            }
+        }
+        reaches
+    }
+
+    /// The fewest edges from the entry block to every block, `None` for unreachable blocks
+    pub fn distances_from_entry(&self) -> Vec<Option<usize>> {
+        let mut distances = vec![None; self.block_count()];
+        if self.block_count() == 0 {
+            return distances;
+        }
+        distances[0] = Some(0);
+        let mut queue = VecDeque::from([0]);
+        while let Some(bb) = queue.pop_front() {
+            let distance = distances[bb].map(|distance| distance + 1);
+            for &succ in self.succs[bb].iter() {
+                if distances[succ].is_none() {
+                    distances[succ] = distance;
+                    queue.push_back(succ);
+                }
+            }
+        }
+        distances
+    }
+
+    /// The blocks every block can branch to, by name
+    pub fn adjacency(&self) -> HashMap<String, Vec<String>> {
+        self.names
+            .iter()
+            .zip(self.succs.iter())
+            .map(|(name, succs)| {
+                let succs = succs.iter().map(|&succ| self.names[succ].clone()).collect();
+                (name.clone(), succs)
+            })
+            .collect()
+    }
+}
+
+/// The blocks reachable from `root` in reverse post-order of a depth-first walk over `succs`
+pub fn reverse_post_order(root: usize, succs: &[Vec<usize>]) -> Vec<usize> {
+    let mut visited = vec![false; succs.len()];
+    let mut post_order = Vec::with_capacity(succs.len());
+    // (block, index of the next successor to visit)
+    let mut stack = vec![(root, 0)];
+    visited[root] = true;
+    while let Some((bb, next)) = stack.last_mut() {
+        let bb = *bb;
+        if let Some(&succ) = succs[bb].get(*next) {
+            *next += 1;
+            if !visited[succ] {
+                visited[succ] = true;
+                stack.push((succ, 0));
+            }
+        } else {
+            post_order.push(bb);
+            stack.pop();
+        }
+    }
+    post_order.reverse();
+    post_order
+}
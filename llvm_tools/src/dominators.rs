@@ -0,0 +1,241 @@
+use crate::cfg::{reverse_post_order, Cfg};
+
+/// The immediate dominator of every block, `None` for the entry block and blocks
+/// unreachable from it
+pub fn immediate_dominators(cfg: &Cfg) -> Vec<Option<usize>> {
+    if cfg.block_count() == 0 {
+        return Vec::new();
+    }
+    idoms(0, &cfg.succs, &cfg.preds)
+}
+
+/// The immediate post-dominator of every block, `None` for blocks post-dominated by no
+/// other block, such as blocks leaving the function, and blocks that never reach a
+/// block leaving the function
+pub fn immediate_post_dominators(cfg: &Cfg) -> Vec<Option<usize>> {
+    // the reverse control flow graph, rooted at a virtual exit following every block
+    // without successors
+    let exit = cfg.block_count();
+    let mut succs = cfg.preds.clone();
+    let mut preds = cfg.succs.clone();
+    succs.push(Vec::new());
+    preds.push(Vec::new());
+    for bb in 0..exit {
+        if cfg.succs[bb].is_empty() {
+            succs[exit].push(bb);
+            preds[bb].push(exit);
+        }
+    }
+    let mut ipdom = idoms(exit, &succs, &preds);
+    ipdom.pop();
+    for bb_ipdom in ipdom.iter_mut() {
+        if *bb_ipdom == Some(exit) {
+            *bb_ipdom = None;
+        }
+    }
+    ipdom
+}
+
+/// The dominance frontier of every block, in block order: the blocks where its
+/// dominance ends, those with a predecessor it dominates that it does not strictly
+/// dominate itself
+pub fn dominance_frontiers(cfg: &Cfg, idom: &[Option<usize>]) -> Vec<Vec<usize>> {
+    let reachable = |bb: usize| bb == 0 || idom[bb].is_some();
+    let mut frontiers: Vec<Vec<usize>> = vec![Vec::new(); cfg.block_count()];
+    for bb in (0..cfg.block_count()).filter(|&bb| reachable(bb)) {
+        if cfg.preds[bb].len() < 2 {
+            continue;
+        }
+        for &pred in cfg.preds[bb].iter().filter(|&&pred| reachable(pred)) {
+            let mut runner = Some(pred);
+            while let Some(current) = runner {
+                if runner == idom[bb] {
+                    break;
+                }
+                if !frontiers[current].contains(&bb) {
+                    frontiers[current].push(bb);
+                }
+                runner = idom[current];
+            }
+        }
+    }
+    for frontier in frontiers.iter_mut() {
+        frontier.sort_unstable();
+    }
+    frontiers
+}
+
+/// The depth of every block in the tree given by immediate (post-)dominators, blocks
+/// without one at 0
+pub fn tree_depths(idom: &[Option<usize>]) -> Vec<usize> {
+    (0..idom.len())
+        .map(|bb| {
+            let mut depth = 0;
+            let mut current = bb;
+            while let Some(parent) = idom[current] {
+                depth += 1;
+                current = parent;
+            }
+            depth
+        })
+        .collect()
+}
+
+/// The number of blocks every block strictly (post-)dominates
+pub fn dominated_counts(idom: &[Option<usize>]) -> Vec<usize> {
+    let mut counts = vec![0; idom.len()];
+    for bb in 0..idom.len() {
+        let mut current = bb;
+        while let Some(parent) = idom[current] {
+            counts[parent] += 1;
+            current = parent;
+        }
+    }
+    counts
+}
+
+/// Whether `a` dominates `b` given the immediate dominators, every block dominates itself
+pub fn dominates(idom: &[Option<usize>], a: usize, b: usize) -> bool {
+    let mut bb = Some(b);
+    while let Some(current) = bb {
+        if current == a {
+            return true;
+        }
+        bb = idom[current];
+    }
+    false
+}
+
+// Cooper, Harvey and Kennedy, "A Simple, Fast Dominance Algorithm"
+fn idoms(root: usize, succs: &[Vec<usize>], preds: &[Vec<usize>]) -> Vec<Option<usize>> {
+    let rpo = reverse_post_order(root, succs);
+    let mut rpo_number = vec![usize::MAX; succs.len()];
+    for (number, &bb) in rpo.iter().enumerate() {
+        rpo_number[bb] = number;
+    }
+
+    let mut idom: Vec<Option<usize>> = vec![None; succs.len()];
+    idom[root] = Some(root);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &bb in rpo.iter().skip(1) {
+            let mut new_idom: Option<usize> = None;
+            for &pred in preds[bb].iter().filter(|&&pred| idom[pred].is_some()) {
+                new_idom = Some(match new_idom {
+                    None => pred,
+                    Some(other) => intersect(&idom, &rpo_number, pred, other),
+                });
+            }
+            if new_idom != idom[bb] {
+                idom[bb] = new_idom;
+                changed = true;
+            }
+        }
+    }
+    idom[root] = None;
+    idom
+}
+
+// the closest common dominator of two processed blocks
+fn intersect(idom: &[Option<usize>], rpo_number: &[usize], mut a: usize, mut b: usize) -> usize {
+    while a != b {
+        while rpo_number[a] > rpo_number[b] {
+            a = idom[a].expect("processed blocks have a dominator");
+        }
+        while rpo_number[b] > rpo_number[a] {
+            b = idom[b].expect("processed blocks have a dominator");
+        }
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loops::natural_loops;
+
+    // a CFG of `block_count` blocks named by position
+    fn cfg(block_count: usize, edges: &[(usize, usize)]) -> Cfg {
+        let mut succs = vec![Vec::new(); block_count];
+        let mut preds = vec![Vec::new(); block_count];
+        for &(from, to) in edges {
+            succs[from].push(to);
+            preds[to].push(from);
+        }
+        Cfg {
+            names: (0..block_count).map(|bb| format!("%{}", bb)).collect(),
+            succs,
+            preds,
+        }
+    }
+
+    #[test]
+    fn multiple_exits_are_post_dominated_by_the_virtual_exit() {
+        // 0 branches to 1 and 2, which return through 3 and 4
+        let cfg = cfg(5, &[(0, 1), (0, 2), (1, 3), (2, 4)]);
+        let idom = immediate_dominators(&cfg);
+        assert_eq!(idom, [None, Some(0), Some(0), Some(1), Some(2)]);
+        assert_eq!(
+            immediate_post_dominators(&cfg),
+            [None, Some(3), Some(4), None, None]
+        );
+        assert!(dominance_frontiers(&cfg, &idom).iter().all(Vec::is_empty));
+        assert_eq!(dominated_counts(&idom), [4, 1, 1, 0, 0]);
+        assert_eq!(tree_depths(&idom), [0, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn irreducible_loops_have_no_header() {
+        // 1 and 2 branch to each other and are both entered from 0
+        let cfg = cfg(4, &[(0, 1), (0, 2), (1, 2), (2, 1), (1, 3)]);
+        let idom = immediate_dominators(&cfg);
+        assert_eq!(idom, [None, Some(0), Some(0), Some(1)]);
+        assert_eq!(
+            dominance_frontiers(&cfg, &idom),
+            [vec![], vec![2], vec![1], vec![]]
+        );
+        assert_eq!(
+            immediate_post_dominators(&cfg),
+            [Some(1), Some(3), Some(1), None]
+        );
+        assert!(natural_loops(&cfg, &idom).is_empty());
+    }
+
+    #[test]
+    fn unreachable_blocks_have_no_dominator() {
+        // 2 is never entered but branches to 1
+        let cfg = cfg(3, &[(0, 1), (2, 1)]);
+        let idom = immediate_dominators(&cfg);
+        assert_eq!(idom, [None, Some(0), None]);
+        assert!(!dominates(&idom, 0, 2));
+        assert!(dominance_frontiers(&cfg, &idom).iter().all(Vec::is_empty));
+        assert_eq!(tree_depths(&idom), [0, 1, 0]);
+        assert_eq!(immediate_post_dominators(&cfg), [Some(1), None, Some(1)]);
+    }
+
+    #[test]
+    fn infinite_loops_are_post_dominated_by_nothing() {
+        // 1 and 2 loop forever, 3 returns
+        let cfg = cfg(4, &[(0, 1), (1, 2), (2, 1), (0, 3)]);
+        let idom = immediate_dominators(&cfg);
+        assert_eq!(idom, [None, Some(0), Some(1), Some(0)]);
+        assert_eq!(immediate_post_dominators(&cfg), [Some(3), None, None, None]);
+        assert_eq!(
+            dominance_frontiers(&cfg, &idom),
+            [vec![], vec![1], vec![1], vec![]]
+        );
+        assert_eq!(dominated_counts(&idom), [3, 1, 0, 0]);
+        let loops = natural_loops(&cfg, &idom);
+        assert_eq!(loops.len(), 1);
+        assert_eq!((loops[0].header, &loops[0].latches[..]), (1, &[2][..]));
+        assert_eq!(loops[0].blocks, [1, 2]);
+    }
+
+    #[test]
+    fn functions_without_blocks_have_no_dominators() {
+        let cfg = cfg(0, &[]);
+        assert!(immediate_dominators(&cfg).is_empty());
+        assert!(immediate_post_dominators(&cfg).is_empty());
+    }
+}
@@ -1,18 +1,65 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 
-use llvm_ir::{function::{FunctionAttribute, FunctionDeclaration}, Function};
-use pyo3::pyclass;
+use llvm_ir::{
+    function::{FunctionDeclaration, Parameter},
+    module::Linkage,
+    types::Types,
+    Function, Terminator,
+};
+use pyo3::{pyclass, pymethods};
 use rayon::prelude::*;
 
-use crate::bb_features::BBFeatures;
+use crate::{
+    attributes::{function_attribute_names, parameter_attribute_names, MemoryEffects},
+    autophase::{autophase, AUTOPHASE_FEATURE_NAMES},
+    bb_features::{opcode_entropy, BBFeatures},
+    call_site::{CallContext, CallSite},
+    cfg::Cfg,
+    dominators::{
+        dominance_frontiers, dominated_counts, dominates, immediate_dominators,
+        immediate_post_dominators, tree_depths,
+    },
+    inst_count::{inst_count, normalize, INST_COUNT_FEATURE_NAMES},
+    linkage::{dll_storage_class_name, is_local, linkage_name, unnamed_addr_name, visibility_name},
+    loops::{loop_depths, natural_loops, LoopFeatures},
+    memory_features::{gep_results, MemoryFeatures},
+};
+
+// the opcodes of each category counted by `FnFeatures`, calls being counted by
+// `call_count`; `select`, `extractvalue`, `insertvalue`, `vaarg`, `freeze` and the
+// exception handling pads belong to none
+const ARITH_OPCODES: [&str; 21] = [
+    "add", "sub", "mul", "udiv", "sdiv", "urem", "srem", "and", "or", "xor", "shl", "lshr", "ashr",
+    "fadd", "fsub", "fmul", "fdiv", "frem", "fneg", "icmp", "fcmp",
+];
+const MEMORY_OPCODES: [&str; 4] = ["alloca", "load", "store", "getelementptr"];
+const CONVERSION_OPCODES: [&str; 13] = [
+    "trunc",
+    "zext",
+    "sext",
+    "fptrunc",
+    "fpext",
+    "fptoui",
+    "fptosi",
+    "uitofp",
+    "sitofp",
+    "ptrtoint",
+    "inttoptr",
+    "bitcast",
+    "addrspacecast",
+];
+// counted along with every block's terminator
+const CONTROL_OPCODES: [&str; 1] = ["phi"];
+const VECTOR_OPCODES: [&str; 3] = ["extractelement", "insertelement", "shufflevector"];
+const ATOMIC_OPCODES: [&str; 3] = ["fence", "cmpxchg", "atomicrmw"];
 
 #[pyclass]
 #[derive(Clone)]
 /// Features of a function
 pub struct FnFeatures {
     #[pyo3(get)]
-    /// (bb_name, callee_name)
-    pub calls: HashSet<(String, String)>,
+    /// Every call-like site in the function, in block and instruction order
+    pub calls: Vec<CallSite>,
     #[pyo3(get)]
     /// The name of the function
     pub name: String,
@@ -26,44 +73,333 @@ pub struct FnFeatures {
     /// The number of instructions in the function
     pub instruction_count: u64,
     #[pyo3(get)]
+    /// The number of instructions per opcode, summed over the blocks
+    pub histogram: HashMap<String, usize>,
+    #[pyo3(get)]
+    /// The Shannon entropy of `histogram`, in bits
+    pub opcode_entropy: f64,
+    #[pyo3(get)]
+    /// How many instruction results have each type, summed over the blocks
+    pub result_types: HashMap<String, usize>,
+    #[pyo3(get)]
+    /// How many instruction operands have each type, summed over the blocks
+    pub operand_types: HashMap<String, usize>,
+    #[pyo3(get)]
+    /// Details of the memory operations, summed over the blocks
+    pub memory: MemoryFeatures,
+    #[pyo3(get)]
+    /// The number of call-like sites, as `BBFeatures::call_count` counts them
+    pub call_count: u64,
+    #[pyo3(get)]
+    /// The number of integer and floating point arithmetic, logic and comparison
+    /// instructions
+    pub arith_count: u64,
+    #[pyo3(get)]
+    /// The number of `alloca`, `load`, `store` and `getelementptr` instructions
+    pub memory_count: u64,
+    #[pyo3(get)]
+    /// The number of cast instructions
+    pub conversion_count: u64,
+    #[pyo3(get)]
+    /// The number of terminators, one per block, and `phi` instructions
+    pub control_count: u64,
+    #[pyo3(get)]
+    /// The number of vector element and shuffle instructions
+    pub vector_count: u64,
+    #[pyo3(get)]
+    /// The number of `fence`, `cmpxchg` and `atomicrmw` instructions
+    pub atomic_count: u64,
+    #[pyo3(get)]
+    /// The share of `load` and `store` instructions, 0 for declarations
+    pub mem_access_ratio: f64,
+    #[pyo3(get)]
     /// Whether the function has var args
     pub has_var_args: bool,
     #[pyo3(get)]
     /// The number of basic blocks in the function
     pub bb_count: u64,
     #[pyo3(get)]
+    /// The function attributes as LLVM spells them, sorted
+    pub attributes: Vec<String>,
+    #[pyo3(get)]
+    /// The attributes of the return value, sorted
+    pub return_attributes: Vec<String>,
+    #[pyo3(get)]
+    /// The attributes of every parameter, sorted
+    pub parameter_attributes: Vec<Vec<String>>,
+    /// What the function may do to memory, from its `memory(...)` attribute; filled in
+    /// by `ModFeatures::read_memory_effects`
+    pub memory_effects: MemoryEffects,
+    #[pyo3(get)]
+    /// Whether the function has the `inlinehint` attribute
+    pub has_inline_hint: bool,
+    #[pyo3(get)]
     /// Whether the function has the `alwaysinline` attribute
     pub has_always_inline: bool,
     #[pyo3(get)]
     /// Whether the function has the `noinline` attribute
     pub has_no_inline: bool,
     #[pyo3(get)]
-    /// Whether the function calls itself
+    /// Whether the function is recursive, calling itself directly or through a cycle of
+    /// other functions in the call graph
     pub is_recursive: bool,
     #[pyo3(get)]
+    /// Whether the function calls itself
+    pub is_directly_recursive: bool,
+    #[pyo3(get)]
+    /// The position of the function's strongly connected component in the call graph's
+    /// bottom-up order, `None` for declarations
+    pub scc_id: Option<u64>,
+    #[pyo3(get)]
+    /// The number of functions in the function's strongly connected component, 0 for
+    /// declarations
+    pub scc_size: u64,
+    #[pyo3(get)]
     /// The number of outgoing calls from the function
     pub outgoing_call_count: u64,
+    #[pyo3(get)]
+    /// Whether the function is only declared in the module
+    pub is_declaration: bool,
+    #[pyo3(get)]
+    /// The number of basic blocks reachable from the entry block
+    pub reachable_bb_count: u64,
+    #[pyo3(get)]
+    /// The number of successors of reachable conditional branches and switches, counted
+    /// like LLVM's `FunctionPropertiesInfo`
+    pub conditionally_executed_block_count: u64,
+    #[pyo3(get)]
+    /// The number of distinct edges between blocks
+    pub edge_count: u64,
+    #[pyo3(get)]
+    /// The number of edges from a block with several successors to a block with several
+    /// predecessors
+    pub critical_edge_count: u64,
+    #[pyo3(get)]
+    /// McCabe's cyclomatic complexity of the reachable blocks, edges - blocks + 2 with
+    /// the exit blocks joined in a single exit
+    pub cyclomatic_complexity: u64,
+    #[pyo3(get)]
+    /// The number of blocks without successors, ending in `ret`, `unreachable`, ...
+    pub exit_block_count: u64,
+    #[pyo3(get)]
+    /// The number of blocks unreachable from the entry block
+    pub unreachable_bb_count: u64,
+    #[pyo3(get)]
+    /// The fewest edges from the entry block to the furthest reachable block
+    pub max_cfg_depth: u64,
+    #[pyo3(get)]
+    /// The number of edges to a block dominating their source, the latches of natural
+    /// loops
+    pub back_edge_count: u64,
+    #[pyo3(get)]
+    /// The distinct successors of every block, by name
+    pub cfg: HashMap<String, Vec<String>>,
+    #[pyo3(get)]
+    /// Whether the function has `available_externally` linkage
+    pub is_available_externally: bool,
+    #[pyo3(get)]
+    /// The linkage as LLVM spells it, such as `internal` or `linkonce_odr`
+    pub linkage: String,
+    #[pyo3(get)]
+    /// Whether the linkage is `private` or `internal`
+    pub has_local_linkage: bool,
+    #[pyo3(get)]
+    /// The visibility: `default`, `hidden` or `protected`
+    pub visibility: String,
+    #[pyo3(get)]
+    /// The DLL storage class: `default`, `dllimport` or `dllexport`
+    pub dll_storage_class: String,
+    #[pyo3(get)]
+    /// The name of the function's comdat, if any
+    pub comdat: Option<String>,
+    #[pyo3(get)]
+    /// The section the function is placed in, if any
+    pub section: Option<String>,
+    #[pyo3(get)]
+    /// `unnamed_addr` or `local_unnamed_addr` when the function's address is
+    /// insignificant
+    pub unnamed_addr: Option<String>,
+    #[pyo3(get)]
+    /// The number of direct calls to the function in the module; filled in by
+    /// `ModFeatures`
+    pub static_call_count: u64,
+    #[pyo3(get)]
+    /// The number of distinct functions calling the function directly; filled in by
+    /// `ModFeatures`
+    pub static_caller_count: u64,
+    #[pyo3(get)]
+    /// The number of references to the function other than as the callee of a direct
+    /// call, such as stores of its address; filled in by `ModFeatures`
+    pub address_taken_count: u64,
+    #[pyo3(get)]
+    /// Whether the function is local and its only use is a single direct call, which
+    /// earns LLVM's last-call-to-static inlining bonus; filled in by `ModFeatures`
+    pub is_last_call_to_static: bool,
+    #[pyo3(get)]
+    /// The immediate dominator of every block but the entry block and unreachable ones
+    pub immediate_dominators: HashMap<String, String>,
+    #[pyo3(get)]
+    /// The immediate post-dominator of every block post-dominated by another block
+    pub immediate_post_dominators: HashMap<String, String>,
+    #[pyo3(get)]
+    /// The dominance frontier of every block, in function order
+    pub dominance_frontiers: HashMap<String, Vec<String>>,
+    #[pyo3(get)]
+    /// The natural loops of the function, in header order
+    pub loops: Vec<LoopFeatures>,
+    #[pyo3(get)]
+    /// The number of natural loops in the function
+    pub loop_count: u64,
+    #[pyo3(get)]
+    /// The deepest nesting of natural loops, 0 without loops
+    pub max_loop_depth: u64,
+    #[pyo3(get)]
+    /// The number of uses of the function, plus one if it is visible outside of the
+    /// module; filled in by `ModFeatures`
+    pub users: u64,
+    autophase: Vec<u64>,
+    inst_count: Vec<u64>,
 }
 
 impl FnFeatures {
-    pub fn from_def(function: &Function) -> Self {
+    pub fn from_def(function: &Function, types: &Types, include_terminators: bool) -> Self {
         let name = function.name.to_string();
-        let bb_feats: HashMap<String, BBFeatures> = function
+        let call_context = CallContext::new(function);
+        let gep_results = gep_results(function);
+        let mut bb_feats: HashMap<String, BBFeatures> = function
             .basic_blocks
             .par_iter()
-            .map(|bb| {
-                let bb_feat = BBFeatures::new(bb);
+            .enumerate()
+            .map(|(bb_index, bb)| {
+                let bb_feat = BBFeatures::new(
+                    &name,
+                    bb_index,
+                    bb,
+                    types,
+                    &call_context,
+                    &gep_results,
+                    include_terminators,
+                );
                 (bb_feat.name.clone(), bb_feat)
             })
             .collect();
 
-        let calls = bb_feats
-            .values()
-            .flat_map(|bb| {
-                bb.function_calls
-                    .iter()
-                    .map(|(callee_name, _)| (bb.name.clone(), callee_name.clone()))
+        // predecessors, loops and cold paths are only known once every block's
+        // successors are
+        let cfg = Cfg::new(function);
+        let idom = immediate_dominators(&cfg);
+        let ipdom = immediate_post_dominators(&cfg);
+        let frontiers = dominance_frontiers(&cfg, &idom);
+        let dom_depths = tree_depths(&idom);
+        let postdom_depths = tree_depths(&ipdom);
+        let dominated = dominated_counts(&idom);
+        let reaches_exit = cfg
+            .can_reach((0..cfg.block_count()).filter(|&bb_index| cfg.succs[bb_index].is_empty()));
+        let natural_loops = natural_loops(&cfg, &idom);
+        let depths = loop_depths(cfg.block_count(), &natural_loops);
+        let loops: Vec<LoopFeatures> = natural_loops
+            .iter()
+            .map(|natural_loop| LoopFeatures::new(function, &cfg, natural_loop, &depths))
+            .collect();
+        let max_loop_depth = depths.iter().copied().max().unwrap_or(0) as u64;
+        let reaches_return = cfg.can_reach(
+            function
+                .basic_blocks
+                .iter()
+                .enumerate()
+                .filter(|(_, bb)| matches!(bb.term, Terminator::Ret(_)))
+                .map(|(bb_index, _)| bb_index),
+        );
+        let mut memory = MemoryFeatures::default();
+        for (bb_index, bb_name) in cfg.names.iter().enumerate() {
+            let bb = bb_feats.get_mut(bb_name).expect("every block has features");
+            bb.predecessor_count = cfg.preds[bb_index].len();
+            memory.add(&bb.memory);
+            bb.loop_depth = depths[bb_index];
+            let reachable = bb_index == 0 || idom[bb_index].is_some();
+            bb.dom_tree_depth = reachable.then_some(dom_depths[bb_index]);
+            bb.postdom_tree_depth = reaches_exit[bb_index].then_some(postdom_depths[bb_index]);
+            bb.dominated_block_count = dominated[bb_index];
+            bb.is_always_executed = reaches_exit[0] && dominates(&ipdom, bb_index, 0);
+            for call_site in bb.call_sites.iter_mut() {
+                call_site.loop_depth = depths[bb_index];
+                call_site.is_in_cold_path = !reaches_return[bb_index];
+            }
+        }
+
+        // the dominator trees by block name
+        let block_map = |parents: &[Option<usize>]| -> HashMap<String, String> {
+            parents
+                .iter()
+                .enumerate()
+                .filter_map(|(bb_index, parent)| {
+                    let parent = (*parent)?;
+                    Some((cfg.names[bb_index].clone(), cfg.names[parent].clone()))
+                })
+                .collect()
+        };
+        let immediate_dominators = block_map(&idom);
+        let immediate_post_dominators = block_map(&ipdom);
+        let dominance_frontiers = frontiers
+            .iter()
+            .enumerate()
+            .map(|(bb_index, frontier)| {
+                let frontier = frontier.iter().map(|&bb| cfg.names[bb].clone()).collect();
+                (cfg.names[bb_index].clone(), frontier)
+            })
+            .collect();
+
+        let reachable_bb_count = (0..cfg.block_count())
+            .filter(|&bb_index| bb_index == 0 || idom[bb_index].is_some())
+            .count() as u64;
+        let edge_count = cfg
+            .succs
+            .iter()
+            .map(|succs| succs.len() as u64)
+            .sum::<u64>();
+        let critical_edge_count = cfg
+            .succs
+            .iter()
+            .filter(|succs| succs.len() > 1)
+            .flatten()
+            .filter(|&&succ| cfg.preds[succ].len() > 1)
+            .count() as u64;
+        let distances = cfg.distances_from_entry();
+        // one plus the extra ways out of each reachable decision, the same as edges -
+        // blocks + 2 once every exit block branches to a single virtual exit
+        let cyclomatic_complexity = 1 + cfg
+            .succs
+            .iter()
+            .zip(distances.iter())
+            .filter(|(_, distance)| distance.is_some())
+            .map(|(succs, _)| succs.len().saturating_sub(1) as u64)
+            .sum::<u64>();
+        let exit_block_count = cfg.succs.iter().filter(|succs| succs.is_empty()).count() as u64;
+        let unreachable_bb_count = cfg.block_count() as u64 - reachable_bb_count;
+        let max_cfg_depth = distances.iter().flatten().copied().max().unwrap_or(0) as u64;
+        let back_edge_count = natural_loops
+            .iter()
+            .map(|natural_loop| natural_loop.latches.len() as u64)
+            .sum::<u64>();
+        let conditionally_executed_block_count = function
+            .basic_blocks
+            .iter()
+            .enumerate()
+            .filter(|&(bb_index, _)| bb_index == 0 || idom[bb_index].is_some())
+            .map(|(_, bb)| match &bb.term {
+                Terminator::CondBr(_) => 2,
+                // every case and the default, shared targets included
+                Terminator::Switch(switch) => switch.dests.len() as u64 + 1,
+                _ => 0,
             })
+            .sum::<u64>();
+        let is_available_externally = function.linkage == Linkage::AvailableExternally;
+        let has_local_linkage = is_local(&function.linkage);
+
+        let calls = function
+            .basic_blocks
+            .iter()
+            .flat_map(|bb| bb_feats[&bb.name.to_string()].call_sites.iter().cloned())
             .collect();
         let arg_count = function.parameters.len() as u64;
         let instruction_count = bb_feats
@@ -72,31 +408,104 @@ impl FnFeatures {
             .sum::<u64>();
         let has_var_args = function.is_var_arg;
         let bb_count = bb_feats.len() as u64;
-        let has_always_inline = function
-            .function_attributes
-            .contains(&FunctionAttribute::AlwaysInline);
-        let has_no_inline = function
-            .function_attributes
-            .contains(&FunctionAttribute::NoInline);
-        let is_recursive = bb_feats
+        let attributes = function_attribute_names(&function.function_attributes);
+        let has_inline_hint = attributes.iter().any(|attribute| attribute == "inlinehint");
+        let has_always_inline = attributes
+            .iter()
+            .any(|attribute| attribute == "alwaysinline");
+        let has_no_inline = attributes.iter().any(|attribute| attribute == "noinline");
+        let is_directly_recursive = bb_feats
             .values()
             .any(|bb| bb.function_calls.contains_key(&name));
         let outgoing_call_count = bb_feats
             .values()
             .map(|bb| bb.call_count as u64)
             .sum::<u64>();
+        let histogram = sum_histograms(bb_feats.values().map(|bb| &bb.histogram));
+        let result_types = sum_histograms(bb_feats.values().map(|bb| &bb.result_types));
+        let operand_types = sum_histograms(bb_feats.values().map(|bb| &bb.operand_types));
+        let category_count = |opcodes: &[&str]| {
+            opcodes
+                .iter()
+                .map(|&opcode| histogram.get(opcode).copied().unwrap_or(0) as u64)
+                .sum::<u64>()
+        };
+        let arith_count = category_count(&ARITH_OPCODES);
+        let memory_count = category_count(&MEMORY_OPCODES);
+        let conversion_count = category_count(&CONVERSION_OPCODES);
+        // terminators are counted even when the histogram leaves them out
+        let control_count = bb_count + category_count(&CONTROL_OPCODES);
+        let vector_count = category_count(&VECTOR_OPCODES);
+        let atomic_count = category_count(&ATOMIC_OPCODES);
+        let mem_access_ratio =
+            category_count(&["load", "store"]) as f64 / instruction_count.max(1) as f64;
         Self {
             calls,
             name,
             bb_feats,
             arg_count,
             instruction_count,
+            opcode_entropy: opcode_entropy(&histogram),
+            histogram,
+            result_types,
+            operand_types,
+            memory,
+            call_count: outgoing_call_count,
+            arith_count,
+            memory_count,
+            conversion_count,
+            control_count,
+            vector_count,
+            atomic_count,
+            mem_access_ratio,
             has_var_args,
             bb_count,
+            attributes,
+            return_attributes: parameter_attribute_names(&function.return_attributes),
+            parameter_attributes: parameters_attribute_names(&function.parameters),
+            // only known once the module is parsed by LLVM
+            memory_effects: MemoryEffects::UNKNOWN,
+            has_inline_hint,
             has_always_inline,
             has_no_inline,
-            is_recursive,
+            // the call graph's SCCs add mutual recursion
+            is_recursive: is_directly_recursive,
+            is_directly_recursive,
+            scc_id: None,
+            scc_size: 0,
             outgoing_call_count,
+            is_declaration: false,
+            reachable_bb_count,
+            conditionally_executed_block_count,
+            edge_count,
+            critical_edge_count,
+            cyclomatic_complexity,
+            exit_block_count,
+            unreachable_bb_count,
+            max_cfg_depth,
+            back_edge_count,
+            cfg: cfg.adjacency(),
+            is_available_externally,
+            linkage: linkage_name(&function.linkage),
+            has_local_linkage,
+            visibility: visibility_name(&function.visibility),
+            dll_storage_class: dll_storage_class_name(&function.dll_storage_class),
+            comdat: function.comdat.as_ref().map(|comdat| comdat.name.clone()),
+            section: function.section.clone(),
+            unnamed_addr: function.unnamed_addr.as_ref().map(unnamed_addr_name),
+            static_call_count: 0,
+            static_caller_count: 0,
+            address_taken_count: 0,
+            is_last_call_to_static: false,
+            immediate_dominators,
+            immediate_post_dominators,
+            dominance_frontiers,
+            loop_count: loops.len() as u64,
+            loops,
+            max_loop_depth,
+            users: 0,
+            autophase: autophase(function),
+            inst_count: inst_count(function),
         }
     }
 
@@ -104,18 +513,284 @@ impl FnFeatures {
         let name = decl.name.to_string();
         let arg_count = decl.parameters.len() as u64;
         let has_var_args = decl.is_var_arg;
+        let attributes = function_attribute_names(&decl.function_attributes);
+        let has_inline_hint = attributes.iter().any(|attribute| attribute == "inlinehint");
+        let has_always_inline = attributes
+            .iter()
+            .any(|attribute| attribute == "alwaysinline");
+        let has_no_inline = attributes.iter().any(|attribute| attribute == "noinline");
         Self {
-            calls: HashSet::new(),
+            calls: Vec::new(),
             name,
             bb_feats: HashMap::new(),
             arg_count,
             instruction_count: 0,
+            histogram: HashMap::new(),
+            opcode_entropy: 0.0,
+            result_types: HashMap::new(),
+            operand_types: HashMap::new(),
+            memory: MemoryFeatures::default(),
+            call_count: 0,
+            arith_count: 0,
+            memory_count: 0,
+            conversion_count: 0,
+            control_count: 0,
+            vector_count: 0,
+            atomic_count: 0,
+            mem_access_ratio: 0.0,
             has_var_args,
             bb_count: 0,
-            has_always_inline: false,
-            has_no_inline: false,
+            attributes,
+            return_attributes: parameter_attribute_names(&decl.return_attributes),
+            parameter_attributes: parameters_attribute_names(&decl.parameters),
+            // only known once the module is parsed by LLVM
+            memory_effects: MemoryEffects::UNKNOWN,
+            has_inline_hint,
+            has_always_inline,
+            has_no_inline,
             is_recursive: false,
+            is_directly_recursive: false,
+            scc_id: None,
+            scc_size: 0,
             outgoing_call_count: 0,
+            is_declaration: true,
+            reachable_bb_count: 0,
+            conditionally_executed_block_count: 0,
+            edge_count: 0,
+            critical_edge_count: 0,
+            cyclomatic_complexity: 0,
+            exit_block_count: 0,
+            unreachable_bb_count: 0,
+            max_cfg_depth: 0,
+            back_edge_count: 0,
+            cfg: HashMap::new(),
+            is_available_externally: false,
+            linkage: linkage_name(&decl.linkage),
+            has_local_linkage: is_local(&decl.linkage),
+            visibility: visibility_name(&decl.visibility),
+            dll_storage_class: dll_storage_class_name(&decl.dll_storage_class),
+            comdat: None,
+            section: None,
+            unnamed_addr: None,
+            static_call_count: 0,
+            static_caller_count: 0,
+            address_taken_count: 0,
+            is_last_call_to_static: false,
+            immediate_dominators: HashMap::new(),
+            immediate_post_dominators: HashMap::new(),
+            dominance_frontiers: HashMap::new(),
+            loops: Vec::new(),
+            loop_count: 0,
+            max_loop_depth: 0,
+            users: 0,
+            autophase: vec![0; AUTOPHASE_FEATURE_NAMES.len()],
+            inst_count: vec![0; INST_COUNT_FEATURE_NAMES.len()],
+        }
+    }
+}
+
+#[pymethods]
+impl FnFeatures {
+    /// The function's memory effects as LLVM prints its `memory(...)` attribute,
+    /// `memory(readwrite)` without one
+    #[getter(memory_effects)]
+    pub fn memory_effects_string(&self) -> String {
+        self.memory_effects.to_string()
+    }
+
+    /// Whether the function has the attribute, named as LLVM spells it. `readnone`,
+    /// `readonly`, `writeonly` and `argmemonly` hold when its memory effects are within
+    /// theirs.
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.attributes.iter().any(|attribute| attribute == name)
+            || self.memory_effects.implies(name)
+    }
+
+    /// Whether any parameter of the function has the attribute
+    pub fn has_parameter_attribute(&self, name: &str) -> bool {
+        self.parameter_attributes
+            .iter()
+            .flatten()
+            .any(|attribute| attribute == name)
+    }
+
+    /// Whether the return value of the function has the attribute
+    pub fn has_return_attribute(&self, name: &str) -> bool {
+        self.return_attributes
+            .iter()
+            .any(|attribute| attribute == name)
+    }
+
+    /// The 56 Autophase features of the function, named by `autophase_feature_names()`;
+    /// all zero for declarations
+    pub fn autophase(&self) -> Vec<u64> {
+        self.autophase.clone()
+    }
+
+    /// CompilerGym's 70-dimensional InstCount vector of the function, named by
+    /// `inst_count_feature_names()`; all zero for declarations
+    pub fn inst_count(&self) -> Vec<u64> {
+        self.inst_count.clone()
+    }
+
+    /// The 69 InstCount densities, every count but the total over the instruction count
+    pub fn inst_count_norm(&self) -> Vec<f64> {
+        normalize(&self.inst_count)
+    }
+}
+
+fn sum_histograms<'a>(
+    histograms: impl Iterator<Item = &'a HashMap<String, usize>>,
+) -> HashMap<String, usize> {
+    let mut total: HashMap<String, usize> = HashMap::new();
+    for (key, count) in histograms.flatten() {
+        *total.entry(key.clone()).or_insert(0) += count;
+    }
+    total
+}
+
+fn parameters_attribute_names(parameters: &[Parameter]) -> Vec<Vec<String>> {
+    parameters
+        .iter()
+        .map(|parameter| parameter_attribute_names(&parameter.attributes))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::module;
+
+    fn features(ir: &str, function: &str) -> FnFeatures {
+        features_with(ir, function, true)
+    }
+
+    fn features_with(ir: &str, function: &str, include_terminators: bool) -> FnFeatures {
+        let module = module(ir);
+        let function = module.get_func_by_name(function).unwrap();
+        FnFeatures::from_def(function, &module.types, include_terminators)
+    }
+
+    const CFG_IR: &str = r#"
+define i32 @f(i1 %c, i32 %n) {
+entry:
+  br i1 %c, label %loop, label %exit
+loop:
+  %i = phi i32 [ 0, %entry ], [ %next, %loop ]
+  %next = add i32 %i, 1
+  %d = icmp slt i32 %next, %n
+  br i1 %d, label %loop, label %exit
+exit:
+  ret i32 0
+dead:
+  br label %exit
+}
+
+define void @g(i32 %n) {
+entry:
+  switch i32 %n, label %a [
+    i32 0, label %a
+    i32 1, label %b
+  ]
+a:
+  ret void
+b:
+  unreachable
+}
+"#;
+
+    #[test]
+    fn measures_the_cfg() {
+        let f = features(CFG_IR, "f");
+        assert_eq!(f.edge_count, 5);
+        // every edge out of %entry and %loop ends in a merge
+        assert_eq!(f.critical_edge_count, 4);
+        // two reachable two-way branches
+        assert_eq!(f.cyclomatic_complexity, 3);
+        assert_eq!(f.exit_block_count, 1);
+        assert_eq!(f.unreachable_bb_count, 1);
+        assert_eq!(f.max_cfg_depth, 1);
+        assert_eq!(f.back_edge_count, 1);
+        assert_eq!(f.cfg["%entry"], ["%loop", "%exit"]);
+        assert_eq!(f.cfg["%loop"], ["%loop", "%exit"]);
+        assert!(f.cfg["%exit"].is_empty());
+        assert_eq!(f.cfg["%dead"], ["%exit"]);
+    }
+
+    #[test]
+    fn counts_distinct_edges() {
+        let g = features(CFG_IR, "g");
+        // the default and case 0 share an edge
+        assert_eq!(g.edge_count, 2);
+        assert_eq!(g.critical_edge_count, 0);
+        // a two-way decision, however many blocks leave the function
+        assert_eq!(g.cyclomatic_complexity, 2);
+        // `unreachable` ends the function too
+        assert_eq!(g.exit_block_count, 2);
+        assert_eq!(g.max_cfg_depth, 1);
+        assert_eq!(g.back_edge_count, 0);
+    }
+
+    #[test]
+    fn counts_conditionally_executed_blocks_like_llvm() {
+        assert_eq!(features(CFG_IR, "f").conditionally_executed_block_count, 4);
+        // the default and case 0 both branch to %a but count as two successors
+        assert_eq!(features(CFG_IR, "g").conditionally_executed_block_count, 3);
+    }
+
+    const CATEGORY_IR: &str = r#"
+define i32 @h(ptr %p, i1 %c) {
+entry:
+  %v = load i32, ptr %p
+  br i1 %c, label %a, label %b
+a:
+  store i32 1, ptr %p
+  br label %b
+b:
+  %x = phi i32 [ %v, %entry ], [ 1, %a ]
+  %y = select i1 %c, i32 %x, i32 0
+  %z = add i32 %y, 1
+  %r = call i32 @ext(i32 %z)
+  ret i32 %r
+}
+
+declare i32 @ext(i32)
+"#;
+
+    #[test]
+    fn counts_opcode_categories() {
+        for include_terminators in [true, false] {
+            let h = features_with(CATEGORY_IR, "h", include_terminators);
+            // 3 terminators and the phi
+            assert_eq!(h.control_count, 4);
+            assert_eq!(h.memory_count, 2);
+            assert_eq!(h.arith_count, 1);
+            assert_eq!(h.call_count, 1);
+            assert_eq!(h.conversion_count, 0);
         }
     }
+
+    #[test]
+    fn categories_partition_the_instructions() {
+        let h = features_with(CATEGORY_IR, "h", true);
+        let categorized = h.arith_count
+            + h.memory_count
+            + h.conversion_count
+            + h.control_count
+            + h.vector_count
+            + h.atomic_count
+            + h.call_count;
+        // the select is the only instruction in no category
+        assert_eq!(h.histogram.get("select"), Some(&1));
+        assert_eq!(categorized + 1, h.instruction_count);
+    }
+
+    #[test]
+    fn measures_the_share_of_memory_accesses() {
+        let h = features_with(CATEGORY_IR, "h", true);
+        assert_eq!(h.instruction_count, 10);
+        assert_eq!(h.mem_access_ratio, 0.2);
+        let h = features_with(CATEGORY_IR, "h", false);
+        assert_eq!(h.mem_access_ratio, 2.0 / 7.0);
+    }
 }
@@ -1,10 +1,20 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, VecDeque};
 
 use polars::io::ipc::IpcWriter;
 use polars::prelude::*;
 use pyo3::{prelude::*, types::PyBytes, Bound, PyResult};
 
-use crate::mod_features::ModFeatures;
+use crate::{
+    attributes::{
+        FUNCTION_ATTRIBUTE_COLUMNS, PARAMETER_ATTRIBUTE_COLUMNS, RETURN_ATTRIBUTE_COLUMNS,
+    },
+    fn_features::FnFeatures,
+    llvm_sys_wrapper::{inline_costs, run_tagged_inline_pass},
+    mlgo::mlgo_features,
+    mod_features::ModFeatures,
+    pass_options::PassOptions,
+    remarks::InlineRemark,
+};
 
 /*
 Inline Features per callsite:
@@ -16,30 +26,255 @@ Inline Features per callsite:
 - callee_has_always_inline: bool
 - callee_has_no_inline: bool
 - callee_is_recursive: bool
+- callee_is_directly_recursive: bool
+- callee_scc_id: Option<u64>
+- callee_scc_size: u64
 - callee_outgoing_call_count: u64
 
 - caller_name: String
 - caller_bb_count: u64
 - caller_instruction_count: u64
 - caller_is_recursive: bool
+- caller_is_directly_recursive: bool
+- caller_scc_id: Option<u64>
+- caller_scc_size: u64
 - caller_outgoing_call_count: u64
 
 - caller_to_callee_instr_ratio: f64
+- call_site_id: String
 - bb_name: String
-- llvm_inlining_decision: bool
+- instr_index: u64
+- call_kind: String
+- call_arg_count: u64
+- call_const_arg_count: u64
+- call_alloca_arg_count: u64
+- call_global_arg_count: u64
+- call_result_used: bool
+- call_in_entry_block: bool
+- call_block_position: f64
+- call_loop_depth: u64
+- call_in_cold_path: bool
+- inline_outcome: String, see InlineOutcome
+- was_inlined: Option<bool>, None when the outcome is unknown
+- inline_remark: Option<String>
+- inline_cost: Option<i64>
+- inline_threshold: Option<i64>
+- inline_reason: Option<String>
+- min_inline_threshold: Option<i32>
+- callee_attr_<name>, caller_attr_<name>: bool, for each of FUNCTION_ATTRIBUTE_COLUMNS
+- callee_param_<name>, caller_param_<name>: bool, for each of PARAMETER_ATTRIBUTE_COLUMNS
+- callee_ret_<name>, caller_ret_<name>: bool, for each of RETURN_ATTRIBUTE_COLUMNS
+- callee_linkage, caller_linkage: String
+- callee_has_local_linkage, caller_has_local_linkage: bool
+- callee_visibility, caller_visibility: String
+- callee_dll_storage_class, caller_dll_storage_class: String
+- callee_comdat, caller_comdat: Option<String>
+- callee_section, caller_section: Option<String>
+- callee_unnamed_addr, caller_unnamed_addr: Option<String>
+- callee_static_call_count, caller_static_call_count: u64
+- callee_static_caller_count, caller_static_caller_count: u64
+- callee_address_taken_count, caller_address_taken_count: u64
+- callee_is_last_call_to_static, caller_is_last_call_to_static: bool
 */
 
+/// The pipeline whose inlining decisions label the call sites: the inliner on its own, or
+/// the full `default<O*>` pipeline for an optimization level such as `O2` or `Oz`
+fn inline_pipeline(opt_level: Option<&str>) -> PyResult<String> {
+    match opt_level {
+        None => Ok("cgscc(inline)".to_string()),
+        Some(level @ ("O0" | "O1" | "O2" | "O3" | "Os" | "Oz")) => Ok(format!("default<{}>", level)),
+        Some(level) => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "unknown optimization level {}, expected one of O0, O1, O2, O3, Os, Oz",
+            level
+        ))),
+    }
+}
+
+/// What the labelling run did to a call site
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InlineOutcome {
+    /// The call was inlined into its caller
+    Inlined,
+    /// The call is still in its caller
+    NotInlined,
+    /// The call is gone from its caller without the inliner inlining it, deleted or
+    /// rewritten by another pass of the pipeline
+    Deleted,
+    /// The caller itself is gone, so whether the call was inlined before is not known
+    Unknown,
+}
+
+impl InlineOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InlineOutcome::Inlined => "inlined",
+            InlineOutcome::NotInlined => "not_inlined",
+            InlineOutcome::Deleted => "deleted",
+            InlineOutcome::Unknown => "unknown",
+        }
+    }
+
+    /// Whether the call was inlined, `None` when that is not known, including for calls
+    /// deleted before the inliner may have decided on them
+    pub fn was_inlined(&self) -> Option<bool> {
+        match self {
+            InlineOutcome::Inlined => Some(true),
+            InlineOutcome::NotInlined => Some(false),
+            InlineOutcome::Deleted | InlineOutcome::Unknown => None,
+        }
+    }
+}
+
+/// Runs `pipeline` over the module with tagged call sites and labels every call site with
+/// a callee, by id, with its outcome and the inliner remark matched to it. A call whose
+/// tag is gone from its caller was inlined if the inliner says so, and deleted otherwise.
+fn label_call_sites(
+    mod_features: &ModFeatures,
+    bc: &[u8],
+    pipeline: &str,
+    options: &PassOptions,
+) -> PyResult<HashMap<String, (InlineOutcome, Option<InlineRemark>)>> {
+    let inline_result = run_tagged_inline_pass(bc, pipeline, options)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    // caller_name -> ids of the call sites left in it after inlining
+    let surviving_call_sites = inline_result.surviving_call_sites;
+
+    // Remarks only name the caller and callee. The inliner visits a caller's calls in
+    // instruction order, so for each (caller, callee) pair the n-th inlining remark goes
+    // to the n-th call that vanished and the n-th other remark to the n-th call that
+    // survived; later remarks come from revisits and cloned calls.
+    let mut remarks: HashMap<(String, String), RemarkQueues> = HashMap::new();
+    for remark in inline_result.remarks {
+        let queues = remarks
+            .entry((remark.caller.clone(), remark.callee.clone()))
+            .or_default();
+        if remark.is_inlined() {
+            queues.inlined.push_back(remark);
+        } else {
+            queues.not_inlined.push_back(remark);
+        }
+    }
+
+    let mut labels = HashMap::new();
+    for call_site in mod_features.call_sites.iter() {
+        let Some(callee_name) = &call_site.callee else {
+            continue;
+        };
+        let queues = remarks
+            .entry((call_site.caller.clone(), callee_name.clone()))
+            .or_default();
+        let call_site_id = call_site.id();
+        let label = match surviving_call_sites.get(&call_site.caller) {
+            Some(ids) if ids.contains(&call_site_id) => {
+                (InlineOutcome::NotInlined, queues.not_inlined.pop_front())
+            }
+            Some(_) => match queues.inlined.pop_front() {
+                Some(remark) => (InlineOutcome::Inlined, Some(remark)),
+                None => (InlineOutcome::Deleted, None),
+            },
+            // tags do not survive every transform, so copies of the call elsewhere say
+            // nothing about the original
+            None => (InlineOutcome::Unknown, None),
+        };
+        labels.insert(call_site_id, label);
+    }
+    Ok(labels)
+}
+
+/// The remarks for one (caller, callee) pair, in the order they were emitted
+#[derive(Default)]
+struct RemarkQueues {
+    inlined: VecDeque<InlineRemark>,
+    not_inlined: VecDeque<InlineRemark>,
+}
+
+/// Extracts one row of features per call site, labelled with the inliner's decision.
+///
+/// `opt_level` takes the decisions from the full pipeline at that level instead of the
+/// inliner alone, and `options` configures the pipeline, inline cost thresholds included.
+/// `inline_thresholds` reruns the pipeline once per threshold, as `options.inline_threshold`,
+/// and records the lowest one at which each call site was inlined. `feature_set="mlgo"`
+/// emits the features of LLVM's ML inline advisor instead of the default columns.
 #[pyfunction]
-pub fn extract_inline_features(bc: Bound<PyBytes>) -> PyResult<Vec<u8>> {
-    let mod_features = ModFeatures::new(bc.clone())?;
-    let inlined_mod_features = ModFeatures::inlined_mod_features(bc.as_bytes())
+#[pyo3(signature = (
+    bc,
+    include_terminators = true,
+    opt_level = None,
+    inline_thresholds = None,
+    feature_set = "default",
+    options = None,
+))]
+pub fn extract_inline_features(
+    bc: Bound<PyBytes>,
+    include_terminators: bool,
+    opt_level: Option<&str>,
+    inline_thresholds: Option<Vec<i32>>,
+    feature_set: &str,
+    options: Option<PassOptions>,
+) -> PyResult<Vec<u8>> {
+    if !matches!(feature_set, "default" | "mlgo") {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "unknown feature set {}, expected default or mlgo",
+            feature_set
+        )));
+    }
+    let pipeline = inline_pipeline(opt_level)?;
+    // the inline features don't look at memory effects
+    let mod_features = ModFeatures::new(bc.clone(), include_terminators, false)?;
+    let bc = bc.as_bytes();
+    let options = options.unwrap_or_default();
+    let labels = label_call_sites(&mod_features, bc, &pipeline, &options)?;
+
+    let run_sweep = inline_thresholds.is_some();
+    // call site id -> lowest threshold it was inlined at
+    let mut thresholds = inline_thresholds.unwrap_or_default();
+    thresholds.sort_unstable();
+    thresholds.dedup();
+    // the threshold is a global LLVM option, so the runs take turns
+    let sweep = thresholds
+        .iter()
+        .map(|&threshold| {
+            let options = PassOptions {
+                inline_threshold: Some(threshold),
+                ..options.clone()
+            };
+            label_call_sites(&mod_features, bc, &pipeline, &options)
+                .map(|labels| (threshold, labels))
+        })
+        .collect::<PyResult<Vec<_>>>()?;
+    let mut min_inline_thresholds: HashMap<String, i32> = HashMap::new();
+    for (threshold, sweep_labels) in sweep {
+        for (call_site_id, (outcome, _)) in sweep_labels {
+            if outcome == InlineOutcome::Inlined {
+                min_inline_thresholds.entry(call_site_id).or_insert(threshold);
+            }
+        }
+    }
+
+    let mut df = match feature_set {
+        "mlgo" => mlgo_features(
+            &mod_features,
+            labels,
+            &inline_costs(bc).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?,
+            run_sweep.then_some(&min_inline_thresholds),
+        )?,
+        _ => default_features(&mod_features, labels, &min_inline_thresholds)?,
+    };
+
+    // Serialize to IPC format for cross-language compatibility
+    let mut buf = Vec::new();
+    IpcWriter::new(&mut buf)
+        .finish(&mut df)
         .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
-    // (caller_name, bb_name, callee_name)
-    let callsite_intersections: HashSet<&(String, String, String)> = mod_features
-        .call_sites
-        .intersection(&inlined_mod_features.call_sites)
-        .collect();
+    Ok(buf)
+}
 
+/// Builds the default columns for every call to a function known to the module
+fn default_features(
+    mod_features: &ModFeatures,
+    mut labels: HashMap<String, (InlineOutcome, Option<InlineRemark>)>,
+    min_inline_thresholds: &HashMap<String, i32>,
+) -> PyResult<DataFrame> {
     // define a vec for each column
     let mut callee_name_vec: Vec<String> = vec![];
     let mut callee_instruction_count_vec: Vec<u64> = vec![];
@@ -49,20 +284,51 @@ pub fn extract_inline_features(bc: Bound<PyBytes>) -> PyResult<Vec<u8>> {
     let mut callee_has_always_inline_vec: Vec<bool> = vec![];
     let mut callee_has_no_inline_vec: Vec<bool> = vec![];
     let mut callee_is_recursive_vec: Vec<bool> = vec![];
+    let mut callee_is_directly_recursive_vec: Vec<bool> = vec![];
+    let mut callee_scc_id_vec: Vec<Option<u64>> = vec![];
+    let mut callee_scc_size_vec: Vec<u64> = vec![];
     let mut callee_outgoing_call_count_vec: Vec<u64> = vec![];
 
     let mut caller_name_vec: Vec<String> = vec![];
     let mut caller_bb_count_vec: Vec<u64> = vec![];
     let mut caller_instruction_count_vec: Vec<u64> = vec![];
     let mut caller_is_recursive_vec: Vec<bool> = vec![];
+    let mut caller_is_directly_recursive_vec: Vec<bool> = vec![];
+    let mut caller_scc_id_vec: Vec<Option<u64>> = vec![];
+    let mut caller_scc_size_vec: Vec<u64> = vec![];
     let mut caller_outgoing_call_count_vec: Vec<u64> = vec![];
 
     let mut caller_to_callee_instr_ratio_vec: Vec<f64> = vec![];
+    let mut call_site_id_vec: Vec<String> = vec![];
     let mut bb_name_vec: Vec<String> = vec![];
-    let mut llvm_inlining_decision_vec: Vec<bool> = vec![];
+    let mut instr_index_vec: Vec<u64> = vec![];
+    let mut call_kind_vec: Vec<&str> = vec![];
+    let mut call_arg_count_vec: Vec<u64> = vec![];
+    let mut call_const_arg_count_vec: Vec<u64> = vec![];
+    let mut call_alloca_arg_count_vec: Vec<u64> = vec![];
+    let mut call_global_arg_count_vec: Vec<u64> = vec![];
+    let mut call_result_used_vec: Vec<bool> = vec![];
+    let mut call_in_entry_block_vec: Vec<bool> = vec![];
+    let mut call_block_position_vec: Vec<f64> = vec![];
+    let mut call_loop_depth_vec: Vec<u64> = vec![];
+    let mut call_in_cold_path_vec: Vec<bool> = vec![];
+    let mut inline_outcome_vec: Vec<&str> = vec![];
+    let mut was_inlined_vec: Vec<Option<bool>> = vec![];
+    let mut inline_remark_vec: Vec<Option<String>> = vec![];
+    let mut inline_cost_vec: Vec<Option<i64>> = vec![];
+    let mut inline_threshold_vec: Vec<Option<i64>> = vec![];
+    let mut inline_reason_vec: Vec<Option<String>> = vec![];
+    let mut min_inline_threshold_vec: Vec<Option<i32>> = vec![];
+    let mut callee_feats_vec: Vec<&FnFeatures> = vec![];
+    let mut caller_feats_vec: Vec<&FnFeatures> = vec![];
 
-    for (caller_name, bb_name, callee_name) in mod_features.call_sites.clone() {
-        let (callee_features, caller_features) = match (mod_features.fn_feats.get(&callee_name), mod_features.fn_feats.get(&caller_name)) {
+    for call_site in mod_features.call_sites.iter() {
+        let caller_name = &call_site.caller;
+        let Some(callee_name) = &call_site.callee else {
+            // Indirect calls and inline asm have no callee to inline
+            continue;
+        };
+        let (callee_features, caller_features) = match (mod_features.fn_feats.get(callee_name), mod_features.fn_feats.get(caller_name)) {
             (Some(callee_features), Some(caller_features)) => (callee_features, caller_features),
             (None, Some(_)) => {
                 // Callee is external - this is expected for intrinsics, library calls, etc.
@@ -79,6 +345,8 @@ pub fn extract_inline_features(bc: Bound<PyBytes>) -> PyResult<Vec<u8>> {
             }
         };
 
+        callee_feats_vec.push(callee_features);
+        caller_feats_vec.push(caller_features);
         callee_name_vec.push(callee_name.clone());
         callee_instruction_count_vec.push(callee_features.instruction_count);
         callee_bb_count_vec.push(callee_features.bb_count);
@@ -87,23 +355,47 @@ pub fn extract_inline_features(bc: Bound<PyBytes>) -> PyResult<Vec<u8>> {
         callee_has_always_inline_vec.push(callee_features.has_always_inline);
         callee_has_no_inline_vec.push(callee_features.has_no_inline);
         callee_is_recursive_vec.push(callee_features.is_recursive);
+        callee_is_directly_recursive_vec.push(callee_features.is_directly_recursive);
+        callee_scc_id_vec.push(callee_features.scc_id);
+        callee_scc_size_vec.push(callee_features.scc_size);
         callee_outgoing_call_count_vec.push(callee_features.outgoing_call_count);
 
         caller_name_vec.push(caller_name.clone());
         caller_bb_count_vec.push(caller_features.bb_count);
         caller_instruction_count_vec.push(caller_features.instruction_count);
         caller_is_recursive_vec.push(caller_features.is_recursive);
+        caller_is_directly_recursive_vec.push(caller_features.is_directly_recursive);
+        caller_scc_id_vec.push(caller_features.scc_id);
+        caller_scc_size_vec.push(caller_features.scc_size);
         caller_outgoing_call_count_vec.push(caller_features.outgoing_call_count);
 
         caller_to_callee_instr_ratio_vec.push(
             caller_features.instruction_count as f64 / callee_features.instruction_count as f64,
         );
-        bb_name_vec.push(bb_name.clone());
-        llvm_inlining_decision_vec.push(callsite_intersections.contains(&(
-            caller_name,
-            bb_name,
-            callee_name,
-        )));
+        let call_site_id = call_site.id();
+        let (outcome, remark) = labels
+            .remove(&call_site_id)
+            .unwrap_or((InlineOutcome::Unknown, None));
+        min_inline_threshold_vec.push(min_inline_thresholds.get(&call_site_id).copied());
+        call_site_id_vec.push(call_site_id);
+        bb_name_vec.push(call_site.bb_name.clone());
+        instr_index_vec.push(call_site.instr_index as u64);
+        call_kind_vec.push(call_site.kind.as_str());
+        call_arg_count_vec.push(call_site.arg_count as u64);
+        call_const_arg_count_vec.push(call_site.const_arg_count as u64);
+        call_alloca_arg_count_vec.push(call_site.alloca_arg_count as u64);
+        call_global_arg_count_vec.push(call_site.global_arg_count as u64);
+        call_result_used_vec.push(call_site.is_result_used);
+        call_in_entry_block_vec.push(call_site.is_in_entry_block);
+        call_block_position_vec.push(call_site.block_position);
+        call_loop_depth_vec.push(call_site.loop_depth as u64);
+        call_in_cold_path_vec.push(call_site.is_in_cold_path);
+        inline_outcome_vec.push(outcome.as_str());
+        was_inlined_vec.push(outcome.was_inlined());
+        inline_remark_vec.push(remark.as_ref().map(|remark| remark.kind.clone()));
+        inline_cost_vec.push(remark.as_ref().and_then(|remark| remark.cost));
+        inline_threshold_vec.push(remark.as_ref().and_then(|remark| remark.threshold));
+        inline_reason_vec.push(remark.and_then(|remark| remark.reason));
     }
 
     let mut df = df!(
@@ -115,22 +407,304 @@ pub fn extract_inline_features(bc: Bound<PyBytes>) -> PyResult<Vec<u8>> {
         "callee_has_always_inline" => callee_has_always_inline_vec,
         "callee_has_no_inline" => callee_has_no_inline_vec,
         "callee_is_recursive" => callee_is_recursive_vec,
+        "callee_is_directly_recursive" => callee_is_directly_recursive_vec,
+        "callee_scc_id" => callee_scc_id_vec,
+        "callee_scc_size" => callee_scc_size_vec,
         "callee_outgoing_call_count" => callee_outgoing_call_count_vec,
         "caller_name" => caller_name_vec,
         "caller_bb_count" => caller_bb_count_vec,
         "caller_instruction_count" => caller_instruction_count_vec,
         "caller_is_recursive" => caller_is_recursive_vec,
+        "caller_is_directly_recursive" => caller_is_directly_recursive_vec,
+        "caller_scc_id" => caller_scc_id_vec,
+        "caller_scc_size" => caller_scc_size_vec,
         "caller_outgoing_call_count" => caller_outgoing_call_count_vec,
         "caller_to_callee_instr_ratio" => caller_to_callee_instr_ratio_vec,
+        "call_site_id" => call_site_id_vec,
         "bb_name" => bb_name_vec,
-        "llvm_inlining_decision" => llvm_inlining_decision_vec,
+        "instr_index" => instr_index_vec,
+        "call_kind" => call_kind_vec,
+        "call_arg_count" => call_arg_count_vec,
+        "call_const_arg_count" => call_const_arg_count_vec,
+        "call_alloca_arg_count" => call_alloca_arg_count_vec,
+        "call_global_arg_count" => call_global_arg_count_vec,
+        "call_result_used" => call_result_used_vec,
+        "call_in_entry_block" => call_in_entry_block_vec,
+        "call_block_position" => call_block_position_vec,
+        "call_loop_depth" => call_loop_depth_vec,
+        "call_in_cold_path" => call_in_cold_path_vec,
+        "inline_outcome" => inline_outcome_vec,
+        "was_inlined" => was_inlined_vec,
+        "inline_remark" => inline_remark_vec,
+        "inline_cost" => inline_cost_vec,
+        "inline_threshold" => inline_threshold_vec,
+        "inline_reason" => inline_reason_vec,
+        "min_inline_threshold" => min_inline_threshold_vec,
     )
     .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    for (role, functions) in [("callee", callee_feats_vec), ("caller", caller_feats_vec)] {
+        let mut columns = attribute_columns(role, &functions);
+        columns.extend(linkage_columns(role, &functions));
+        df.hstack_mut(&columns)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    }
+    Ok(df)
+}
 
-    // Serialize to IPC format for cross-language compatibility
-    let mut buf = Vec::new();
-    IpcWriter::new(&mut buf)
-        .finish(&mut df)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
-    Ok(buf)
+// a boolean column per tracked function, parameter and return attribute
+fn attribute_columns(role: &str, functions: &[&FnFeatures]) -> Vec<Column> {
+    let column = |kind: &str, name: &str, has: &dyn Fn(&FnFeatures) -> bool| {
+        Column::new(
+            format!("{}_{}_{}", role, kind, name).into(),
+            functions
+                .iter()
+                .map(|function| has(function))
+                .collect::<Vec<bool>>(),
+        )
+    };
+    let function_columns = FUNCTION_ATTRIBUTE_COLUMNS.iter().map(|name| {
+        column("attr", name, &|function: &FnFeatures| {
+            function.has_attribute(name)
+        })
+    });
+    let parameter_columns = PARAMETER_ATTRIBUTE_COLUMNS.iter().map(|name| {
+        column("param", name, &|function: &FnFeatures| {
+            function.has_parameter_attribute(name)
+        })
+    });
+    let return_columns = RETURN_ATTRIBUTE_COLUMNS.iter().map(|name| {
+        column("ret", name, &|function: &FnFeatures| {
+            function.has_return_attribute(name)
+        })
+    });
+    function_columns
+        .chain(parameter_columns)
+        .chain(return_columns)
+        .collect()
+}
+
+// the linkage and use columns of the callee or caller
+fn linkage_columns(role: &str, functions: &[&FnFeatures]) -> Vec<Column> {
+    let name = |column: &str| -> PlSmallStr { format!("{}_{}", role, column).into() };
+    vec![
+        Column::new(
+            name("linkage"),
+            functions
+                .iter()
+                .map(|function| function.linkage.as_str())
+                .collect::<Vec<&str>>(),
+        ),
+        Column::new(
+            name("has_local_linkage"),
+            functions
+                .iter()
+                .map(|function| function.has_local_linkage)
+                .collect::<Vec<bool>>(),
+        ),
+        Column::new(
+            name("visibility"),
+            functions
+                .iter()
+                .map(|function| function.visibility.as_str())
+                .collect::<Vec<&str>>(),
+        ),
+        Column::new(
+            name("dll_storage_class"),
+            functions
+                .iter()
+                .map(|function| function.dll_storage_class.as_str())
+                .collect::<Vec<&str>>(),
+        ),
+        Column::new(
+            name("comdat"),
+            functions
+                .iter()
+                .map(|function| function.comdat.as_deref())
+                .collect::<Vec<Option<&str>>>(),
+        ),
+        Column::new(
+            name("section"),
+            functions
+                .iter()
+                .map(|function| function.section.as_deref())
+                .collect::<Vec<Option<&str>>>(),
+        ),
+        Column::new(
+            name("unnamed_addr"),
+            functions
+                .iter()
+                .map(|function| function.unnamed_addr.as_deref())
+                .collect::<Vec<Option<&str>>>(),
+        ),
+        Column::new(
+            name("static_call_count"),
+            functions
+                .iter()
+                .map(|function| function.static_call_count)
+                .collect::<Vec<u64>>(),
+        ),
+        Column::new(
+            name("static_caller_count"),
+            functions
+                .iter()
+                .map(|function| function.static_caller_count)
+                .collect::<Vec<u64>>(),
+        ),
+        Column::new(
+            name("address_taken_count"),
+            functions
+                .iter()
+                .map(|function| function.address_taken_count)
+                .collect::<Vec<u64>>(),
+        ),
+        Column::new(
+            name("is_last_call_to_static"),
+            functions
+                .iter()
+                .map(|function| function.is_last_call_to_static)
+                .collect::<Vec<bool>>(),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::bitcode;
+
+    const IR: &str = r#"
+define internal i32 @leaf(i32 %x) {
+entry:
+  %y = add i32 %x, 1
+  ret i32 %y
+}
+
+@sink = global i32 0
+
+define i32 @big(i32 %x) noinline {
+entry:
+  store i32 %x, ptr @sink
+  ret i32 %x
+}
+
+define internal i32 @mid(i32 %x) {
+entry:
+  %y = call i32 @big(i32 %x)
+  ret i32 %y
+}
+
+define i32 @main(i32 %x) {
+entry:
+  %a = call i32 @leaf(i32 %x)
+  %b = call i32 @big(i32 %a)
+  %c = call i32 @mid(i32 %b)
+  ret i32 %c
+}
+
+define i32 @pure(i32 %x) noinline {
+entry:
+  %y = mul i32 %x, %x
+  ret i32 %y
+}
+
+define i32 @main2(i32 %x) {
+entry:
+  %unused = call i32 @pure(i32 %x)
+  %a = call i32 @leaf(i32 %x)
+  ret i32 %a
+}
+"#;
+
+    fn labels(pipeline: &str) -> HashMap<String, (InlineOutcome, Option<InlineRemark>)> {
+        let bc = bitcode(IR);
+        let mod_features = ModFeatures::from_bc(&bc, true).unwrap();
+        label_call_sites(&mod_features, &bc, pipeline, &PassOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn labels_calls_by_their_tags() {
+        let labels = labels("cgscc(inline)");
+        assert_eq!(labels["main:0:0"].0, InlineOutcome::Inlined);
+        assert_eq!(labels["main:0:1"].0, InlineOutcome::NotInlined);
+        assert_eq!(labels["main:0:2"].0, InlineOutcome::Inlined);
+        assert!(labels["main:0:0"]
+            .1
+            .as_ref()
+            .is_some_and(|remark| remark.is_inlined()));
+    }
+
+    #[test]
+    fn calls_in_deleted_callers_are_unknown() {
+        // @mid is inlined into @main and deleted, its call to @big surviving as a copy
+        let labels = labels("cgscc(inline)");
+        assert_eq!(labels["mid:0:0"].0, InlineOutcome::Unknown);
+        assert_eq!(labels["mid:0:0"].0.was_inlined(), None);
+    }
+
+    #[test]
+    fn labels_full_pipelines_by_tags() {
+        let labels = labels("default<O2>");
+        assert_eq!(labels["main:0:0"].0, InlineOutcome::Inlined);
+        assert_eq!(labels["main:0:1"].0, InlineOutcome::NotInlined);
+        assert_eq!(labels["main2:0:1"].0, InlineOutcome::Inlined);
+        assert_eq!(labels["mid:0:0"].0, InlineOutcome::Unknown);
+    }
+
+    #[test]
+    fn calls_removed_by_other_passes_are_deleted() {
+        // the unused call to the pure @pure is dead code at O2
+        let labels = labels("default<O2>");
+        assert_eq!(labels["main2:0:0"].0, InlineOutcome::Deleted);
+        assert_eq!(labels["main2:0:0"].0.was_inlined(), None);
+        assert_eq!(labels["main2:0:0"].0.as_str(), "deleted");
+    }
+
+    #[test]
+    fn inline_thresholds_reach_the_cost_analysis() {
+        // 30 volatile stores cost well over nothing and well under the default thresholds
+        let ir = format!(
+            "@sink = global i32 0
+define void @hinted(i32 %x) inlinehint {{
+entry:
+{}  ret void
+}}
+define void @caller(i32 %x) {{
+entry:
+  call void @hinted(i32 %x)
+  ret void
+}}
+",
+            "  store volatile i32 %x, ptr @sink\n".repeat(30)
+        );
+        let bc = bitcode(&ir);
+        let mod_features = ModFeatures::from_bc(&bc, true).unwrap();
+        let outcome = |options: PassOptions| {
+            label_call_sites(&mod_features, &bc, "cgscc(inline)", &options).unwrap()["caller:0:0"].0
+        };
+
+        assert_eq!(outcome(PassOptions::default()), InlineOutcome::Inlined);
+        // a hinted callee gets the higher of the two thresholds
+        let low = PassOptions {
+            inline_threshold: Some(0),
+            inline_hint_threshold: Some(0),
+            ..PassOptions::default()
+        };
+        assert_eq!(outcome(low.clone()), InlineOutcome::NotInlined);
+        let hinted = PassOptions {
+            inline_hint_threshold: Some(1000),
+            ..low
+        };
+        assert_eq!(outcome(hinted), InlineOutcome::Inlined);
+        // each threshold is back to its default once a run is over
+        let low_default = PassOptions {
+            inline_hint_threshold: Some(0),
+            ..PassOptions::default()
+        };
+        assert_eq!(outcome(low_default), InlineOutcome::Inlined);
+        let low_hint = PassOptions {
+            inline_threshold: Some(0),
+            ..PassOptions::default()
+        };
+        assert_eq!(outcome(low_hint), InlineOutcome::Inlined);
+    }
 }
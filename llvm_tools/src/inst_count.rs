@@ -0,0 +1,290 @@
+use llvm_ir::{Function, Instruction, Terminator};
+use pyo3::pyfunction;
+
+/// The InstCount features in the order of CompilerGym's observation: the totals, then
+/// one count per opcode in the order of LLVM's Instruction.def
+pub const INST_COUNT_FEATURE_NAMES: [&str; 70] = [
+    "TotalInstsCount",
+    "TotalBlocksCount",
+    "TotalFuncsCount",
+    "RetCount",
+    "BrCount",
+    "SwitchCount",
+    "IndirectBrCount",
+    "InvokeCount",
+    "ResumeCount",
+    "UnreachableCount",
+    "CleanupRetCount",
+    "CatchRetCount",
+    "CatchSwitchCount",
+    "CallBrCount",
+    "FNegCount",
+    "AddCount",
+    "FAddCount",
+    "SubCount",
+    "FSubCount",
+    "MulCount",
+    "FMulCount",
+    "UDivCount",
+    "SDivCount",
+    "FDivCount",
+    "URemCount",
+    "SRemCount",
+    "FRemCount",
+    "ShlCount",
+    "LShrCount",
+    "AShrCount",
+    "AndCount",
+    "OrCount",
+    "XorCount",
+    "AllocaCount",
+    "LoadCount",
+    "StoreCount",
+    "GetElementPtrCount",
+    "FenceCount",
+    "AtomicCmpXchgCount",
+    "AtomicRMWCount",
+    "TruncCount",
+    "ZExtCount",
+    "SExtCount",
+    "FPToUICount",
+    "FPToSICount",
+    "UIToFPCount",
+    "SIToFPCount",
+    "FPTruncCount",
+    "FPExtCount",
+    "PtrToIntCount",
+    "IntToPtrCount",
+    "BitCastCount",
+    "AddrSpaceCastCount",
+    "CleanupPadCount",
+    "CatchPadCount",
+    "ICmpCount",
+    "FCmpCount",
+    "PHICount",
+    "CallCount",
+    "SelectCount",
+    "UserOp1Count",
+    "UserOp2Count",
+    "VAArgCount",
+    "ExtractElementCount",
+    "InsertElementCount",
+    "ShuffleVectorCount",
+    "ExtractValueCount",
+    "InsertValueCount",
+    "LandingPadCount",
+    "FreezeCount",
+];
+
+/// The names of the InstCount features, in the order `inst_count()` returns them
+#[pyfunction]
+pub fn inst_count_feature_names() -> Vec<&'static str> {
+    INST_COUNT_FEATURE_NAMES.to_vec()
+}
+
+/// The names of the normalized InstCount features, in the order `inst_count_norm()`
+/// returns them
+#[pyfunction]
+pub fn inst_count_norm_feature_names() -> Vec<String> {
+    INST_COUNT_FEATURE_NAMES[1..]
+        .iter()
+        .map(|name| name.replace("Count", "Density"))
+        .collect()
+}
+
+/// Computes the InstCount vector of a function definition, terminators included
+pub fn inst_count(function: &Function) -> Vec<u64> {
+    let mut counts = vec![0; INST_COUNT_FEATURE_NAMES.len()];
+    // TotalFuncsCount
+    counts[2] = 1;
+    for bb in function.basic_blocks.iter() {
+        counts[1] += 1;
+        let opcodes = bb
+            .instrs
+            .iter()
+            .map(instruction_feature)
+            .chain(std::iter::once(terminator_feature(&bb.term)));
+        for opcode in opcodes {
+            counts[0] += 1;
+            counts[opcode] += 1;
+        }
+    }
+    counts
+}
+
+/// CompilerGym's InstCountNorm: every count but the total divided by the total
+/// instruction count
+pub fn normalize(counts: &[u64]) -> Vec<f64> {
+    let total = counts[0].max(1) as f64;
+    counts[1..].iter().map(|&count| count as f64 / total).collect()
+}
+
+// the position of the instruction's opcode feature
+fn instruction_feature(instruction: &Instruction) -> usize {
+    match instruction {
+        Instruction::FNeg(_) => 14,
+        Instruction::Add(_) => 15,
+        Instruction::FAdd(_) => 16,
+        Instruction::Sub(_) => 17,
+        Instruction::FSub(_) => 18,
+        Instruction::Mul(_) => 19,
+        Instruction::FMul(_) => 20,
+        Instruction::UDiv(_) => 21,
+        Instruction::SDiv(_) => 22,
+        Instruction::FDiv(_) => 23,
+        Instruction::URem(_) => 24,
+        Instruction::SRem(_) => 25,
+        Instruction::FRem(_) => 26,
+        Instruction::Shl(_) => 27,
+        Instruction::LShr(_) => 28,
+        Instruction::AShr(_) => 29,
+        Instruction::And(_) => 30,
+        Instruction::Or(_) => 31,
+        Instruction::Xor(_) => 32,
+        Instruction::Alloca(_) => 33,
+        Instruction::Load(_) => 34,
+        Instruction::Store(_) => 35,
+        Instruction::GetElementPtr(_) => 36,
+        Instruction::Fence(_) => 37,
+        Instruction::CmpXchg(_) => 38,
+        Instruction::AtomicRMW(_) => 39,
+        Instruction::Trunc(_) => 40,
+        Instruction::ZExt(_) => 41,
+        Instruction::SExt(_) => 42,
+        Instruction::FPToUI(_) => 43,
+        Instruction::FPToSI(_) => 44,
+        Instruction::UIToFP(_) => 45,
+        Instruction::SIToFP(_) => 46,
+        Instruction::FPTrunc(_) => 47,
+        Instruction::FPExt(_) => 48,
+        Instruction::PtrToInt(_) => 49,
+        Instruction::IntToPtr(_) => 50,
+        Instruction::BitCast(_) => 51,
+        Instruction::AddrSpaceCast(_) => 52,
+        Instruction::CleanupPad(_) => 53,
+        Instruction::CatchPad(_) => 54,
+        Instruction::ICmp(_) => 55,
+        Instruction::FCmp(_) => 56,
+        Instruction::Phi(_) => 57,
+        Instruction::Call(_) => 58,
+        Instruction::Select(_) => 59,
+        // UserOp1 and UserOp2 (60, 61) only exist inside passes
+        Instruction::VAArg(_) => 62,
+        Instruction::ExtractElement(_) => 63,
+        Instruction::InsertElement(_) => 64,
+        Instruction::ShuffleVector(_) => 65,
+        Instruction::ExtractValue(_) => 66,
+        Instruction::InsertValue(_) => 67,
+        Instruction::LandingPad(_) => 68,
+        Instruction::Freeze(_) => 69,
+    }
+}
+
+fn terminator_feature(terminator: &Terminator) -> usize {
+    match terminator {
+        Terminator::Ret(_) => 3,
+        Terminator::Br(_) | Terminator::CondBr(_) => 4,
+        Terminator::Switch(_) => 5,
+        Terminator::IndirectBr(_) => 6,
+        Terminator::Invoke(_) => 7,
+        Terminator::Resume(_) => 8,
+        Terminator::Unreachable(_) => 9,
+        Terminator::CleanupRet(_) => 10,
+        Terminator::CatchRet(_) => 11,
+        Terminator::CatchSwitch(_) => 12,
+        Terminator::CallBr(_) => 13,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        autophase::AUTOPHASE_FEATURE_NAMES, mod_features::ModFeatures, test_utils::bitcode,
+    };
+
+    const IR: &str = r#"
+declare i32 @ext(i32)
+
+define i32 @f(i32 %n) {
+entry:
+  %p = alloca i32
+  store i32 0, ptr %p
+  %c = icmp sgt i32 %n, 1
+  br i1 %c, label %then, label %join
+then:
+  %v = load i32, ptr %p
+  %r = call i32 @ext(i32 %v)
+  br label %join
+join:
+  %x = phi i32 [ 1, %entry ], [ %r, %then ]
+  ret i32 %x
+}
+"#;
+
+    fn count(features: &[u64], name: &str) -> u64 {
+        let position = INST_COUNT_FEATURE_NAMES
+            .iter()
+            .position(|&feature| feature == name)
+            .unwrap();
+        features[position]
+    }
+
+    #[test]
+    fn counts_every_opcode_and_the_totals() {
+        let features = ModFeatures::from_bc(&bitcode(IR), true)
+            .unwrap()
+            .inst_count();
+        let expected = [
+            ("TotalInstsCount", 9),
+            ("TotalBlocksCount", 3),
+            ("TotalFuncsCount", 1),
+            ("RetCount", 1),
+            ("BrCount", 2),
+            ("AllocaCount", 1),
+            ("LoadCount", 1),
+            ("StoreCount", 1),
+            ("ICmpCount", 1),
+            ("PHICount", 1),
+            ("CallCount", 1),
+        ];
+        for (name, value) in expected {
+            assert_eq!(count(&features, name), value, "{}", name);
+        }
+        // every instruction is counted under exactly one opcode
+        assert_eq!(features[3..].iter().sum::<u64>(), features[0]);
+    }
+
+    #[test]
+    fn counts_the_same_functions_as_autophase() {
+        let mod_features = ModFeatures::from_bc(&bitcode(IR), true).unwrap();
+        let total_funcs = AUTOPHASE_FEATURE_NAMES
+            .iter()
+            .position(|&name| name == "TotalFuncs")
+            .unwrap();
+        // the declaration of @ext is in neither
+        assert_eq!(
+            mod_features.autophase()[total_funcs],
+            count(&mod_features.inst_count(), "TotalFuncsCount")
+        );
+    }
+
+    #[test]
+    fn normalizes_by_the_instruction_count() {
+        let features = ModFeatures::from_bc(&bitcode(IR), true)
+            .unwrap()
+            .inst_count_norm();
+        let names = inst_count_norm_feature_names();
+        assert_eq!(features.len(), names.len());
+        assert_eq!(names[0], "TotalBlocksDensity");
+        assert_eq!(features[0], 3.0 / 9.0);
+        let br = names.iter().position(|name| name == "BrDensity").unwrap();
+        assert_eq!(features[br], 2.0 / 9.0);
+    }
+
+    #[test]
+    fn normalizes_empty_vectors_to_zero() {
+        let counts = vec![0; INST_COUNT_FEATURE_NAMES.len()];
+        assert!(normalize(&counts).iter().all(|&density| density == 0.0));
+    }
+}
@@ -0,0 +1,476 @@
+use std::collections::HashMap;
+
+use llvm_ir::{
+    types::{FPType, Typed, Types},
+    Constant, Function, Name, Operand, Type,
+};
+use pyo3::{prelude::*, pyclass, pymethods, PyRef, PyResult};
+
+use crate::{
+    bb_features::{instruction_to_string, terminator_to_string},
+    cfg::{reverse_post_order, Cfg},
+    mod_features::ModFeatures,
+    operands::{instruction_operands, terminator_operands},
+};
+
+// IR2Vec's weights for the opcode, type and argument parts of an instruction
+const OPCODE_WEIGHT: f64 = 1.0;
+const TYPE_WEIGHT: f64 = 0.5;
+const ARG_WEIGHT: f64 = 0.2;
+
+#[pyclass]
+/// A seed embedding vocabulary composing IR2Vec-style symbolic embeddings. An
+/// instruction is `1.0 * opcode + 0.5 * type + 0.2 * sum(arguments)`, a block the sum
+/// of its instructions and a function the sum of its blocks. Entities missing from the
+/// vocabulary contribute nothing.
+pub struct Ir2Vec {
+    // lowercased entity -> embedding
+    vocab: HashMap<String, Vec<f64>>,
+    #[pyo3(get)]
+    /// The length of every embedding
+    pub dimension: usize,
+}
+
+#[pymethods]
+impl Ir2Vec {
+    /// Loads a vocabulary of `entity:[v1, v2, ...]` lines, the format of IR2Vec's seed
+    /// embedding files. Entities match case-insensitively.
+    #[new]
+    pub fn new(vocab_path: &str) -> PyResult<Self> {
+        let text = std::fs::read_to_string(vocab_path)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        Self::parse(&text).map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+    }
+
+    /// The embedding of every instruction of a function by block, terminators last.
+    /// `flow_aware` replaces the embedding of an argument defined by another instruction
+    /// with that instruction's embedding, following use-def chains.
+    #[pyo3(signature = (mod_features, function, flow_aware = false))]
+    pub fn instruction_embeddings(
+        &self,
+        mod_features: PyRef<ModFeatures>,
+        function: &str,
+        flow_aware: bool,
+    ) -> PyResult<HashMap<String, Vec<Vec<f64>>>> {
+        let func = find_function(&mod_features, function)?;
+        let embeddings = self.embed_instructions(func, flow_aware);
+        Ok(func.blocks.iter().cloned().zip(embeddings).collect())
+    }
+
+    /// The embedding of every block of a function
+    #[pyo3(signature = (mod_features, function, flow_aware = false))]
+    pub fn bb_embeddings(
+        &self,
+        mod_features: PyRef<ModFeatures>,
+        function: &str,
+        flow_aware: bool,
+    ) -> PyResult<HashMap<String, Vec<f64>>> {
+        let func = find_function(&mod_features, function)?;
+        let embeddings = self.embed_instructions(func, flow_aware);
+        Ok(func
+            .blocks
+            .iter()
+            .cloned()
+            .zip(embeddings.iter().map(|instrs| self.sum(instrs)))
+            .collect())
+    }
+
+    /// The embedding of a function
+    #[pyo3(signature = (mod_features, function, flow_aware = false))]
+    pub fn fn_embedding(
+        &self,
+        mod_features: PyRef<ModFeatures>,
+        function: &str,
+        flow_aware: bool,
+    ) -> PyResult<Vec<f64>> {
+        let func = find_function(&mod_features, function)?;
+        Ok(self.embed_function(func, flow_aware))
+    }
+
+    /// The embedding of every function defined in the module
+    #[pyo3(signature = (mod_features, flow_aware = false))]
+    pub fn fn_embeddings(
+        &self,
+        mod_features: PyRef<ModFeatures>,
+        flow_aware: bool,
+    ) -> PyResult<HashMap<String, Vec<f64>>> {
+        Ok(symbolic_functions(&mod_features)?
+            .iter()
+            .map(|(name, func)| (name.clone(), self.embed_function(func, flow_aware)))
+            .collect())
+    }
+}
+
+impl Ir2Vec {
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut vocab = HashMap::new();
+        let mut dimension = None;
+        for (line_index, line) in text.lines().enumerate() {
+            let line = line.trim().trim_end_matches(',');
+            if line.is_empty() {
+                continue;
+            }
+            let malformed = || format!("line {}: expected entity:[v1, v2, ...]", line_index + 1);
+            let (entity, values) = line.split_once(':').ok_or_else(malformed)?;
+            let values = values
+                .trim()
+                .strip_prefix('[')
+                .and_then(|values| values.strip_suffix(']'))
+                .ok_or_else(malformed)?;
+            let values = values
+                .split(',')
+                .map(|value| value.trim().parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>()
+                .map_err(|e| format!("line {}: {}", line_index + 1, e))?;
+            match dimension {
+                None => dimension = Some(values.len()),
+                Some(dimension) if dimension != values.len() => {
+                    return Err(format!(
+                        "line {}: expected {} values, found {}",
+                        line_index + 1,
+                        dimension,
+                        values.len()
+                    ))
+                }
+                Some(_) => {}
+            }
+            vocab.insert(entity.trim().to_lowercase(), values);
+        }
+        let dimension = dimension.ok_or("the vocabulary is empty")?;
+        Ok(Self { vocab, dimension })
+    }
+
+    fn embed_function(&self, function: &SymbolicFunction, flow_aware: bool) -> Vec<f64> {
+        let blocks = self.embed_instructions(function, flow_aware);
+        self.sum(blocks.iter().flatten())
+    }
+
+    // every instruction's embedding, by block in function order
+    fn embed_instructions(
+        &self,
+        function: &SymbolicFunction,
+        flow_aware: bool,
+    ) -> Vec<Vec<Vec<f64>>> {
+        let mut embeddings: Vec<Vec<Vec<f64>>> = vec![Vec::new(); function.blocks.len()];
+        let order: Vec<usize> = if flow_aware {
+            function.flow_order.clone()
+        } else {
+            (0..function.blocks.len()).collect()
+        };
+        for bb_index in order {
+            for instr in function.instrs[bb_index].iter() {
+                let embedding = self.compose(instr, &embeddings, flow_aware);
+                embeddings[bb_index].push(embedding);
+            }
+        }
+        embeddings
+    }
+
+    // an instruction's embedding; a flow-aware argument takes the embedding of the
+    // instruction defining it once that one has been embedded
+    fn compose(
+        &self,
+        instr: &SymbolicInstr,
+        embeddings: &[Vec<Vec<f64>>],
+        flow_aware: bool,
+    ) -> Vec<f64> {
+        let mut embedding = vec![0.0; self.dimension];
+        add(&mut embedding, OPCODE_WEIGHT, self.vocab.get(instr.opcode));
+        add(&mut embedding, TYPE_WEIGHT, self.vocab.get(instr.ty));
+        for argument in instr.arguments.iter() {
+            let argument = argument
+                .definition
+                .filter(|_| flow_aware)
+                .and_then(|(bb_index, instr_index)| embeddings[bb_index].get(instr_index))
+                .or_else(|| argument.entity.and_then(|entity| self.entity(entity)));
+            add(&mut embedding, ARG_WEIGHT, argument);
+        }
+        embedding
+    }
+
+    // the embedding of an operand kind, named as in IR2Vec or in LLVM's vocabulary
+    fn entity(&self, entity: &str) -> Option<&Vec<f64>> {
+        self.vocab
+            .get(entity)
+            .or_else(|| self.vocab.get(&format!("{}id", entity)))
+    }
+
+    fn sum<'a>(&self, embeddings: impl IntoIterator<Item = &'a Vec<f64>>) -> Vec<f64> {
+        let mut total = vec![0.0; self.dimension];
+        for embedding in embeddings {
+            add(&mut total, 1.0, Some(embedding));
+        }
+        total
+    }
+}
+
+/// A defined function reduced to what IR2Vec embeds, computed once when the module
+/// is parsed
+pub struct SymbolicFunction {
+    blocks: Vec<String>,
+    // blocks in reverse post-order, unreachable blocks last, so that definitions come
+    // before their uses except around back edges
+    flow_order: Vec<usize>,
+    // every instruction by block, terminators last
+    instrs: Vec<Vec<SymbolicInstr>>,
+}
+
+struct SymbolicInstr {
+    opcode: &'static str,
+    ty: &'static str,
+    arguments: Vec<Argument>,
+}
+
+struct Argument {
+    // `None` for metadata
+    entity: Option<&'static str>,
+    // the block and index of the instruction defining the argument
+    definition: Option<(usize, usize)>,
+}
+
+impl SymbolicFunction {
+    pub fn new(function: &Function, types: &Types) -> Self {
+        let cfg = Cfg::new(function);
+        let mut flow_order = if cfg.block_count() > 0 {
+            reverse_post_order(0, &cfg.succs)
+        } else {
+            Vec::new()
+        };
+        let mut visited = vec![false; cfg.block_count()];
+        flow_order
+            .iter()
+            .for_each(|&bb_index| visited[bb_index] = true);
+        flow_order.extend((0..cfg.block_count()).filter(|&bb_index| !visited[bb_index]));
+
+        // instruction result -> (block, index); invoke results keep their entity
+        let mut definitions: HashMap<&Name, (usize, usize)> = HashMap::new();
+        for (bb_index, bb) in function.basic_blocks.iter().enumerate() {
+            for (instr_index, instr) in bb.instrs.iter().enumerate() {
+                if let Some(result) = instr.try_get_result() {
+                    definitions.insert(result, (bb_index, instr_index));
+                }
+            }
+        }
+        let arguments = |operands: Vec<&Operand>| -> Vec<Argument> {
+            operands
+                .into_iter()
+                .map(|operand| Argument {
+                    entity: operand_entity(operand),
+                    definition: match operand {
+                        Operand::LocalOperand { name, .. } => definitions.get(name).copied(),
+                        _ => None,
+                    },
+                })
+                .collect()
+        };
+
+        let instrs = function
+            .basic_blocks
+            .iter()
+            .map(|bb| {
+                let mut instrs: Vec<SymbolicInstr> = bb
+                    .instrs
+                    .iter()
+                    .map(|instr| SymbolicInstr {
+                        opcode: opcode_entity(instruction_to_string(instr)),
+                        ty: type_entity(&instr.get_type(types)),
+                        arguments: arguments(instruction_operands(instr)),
+                    })
+                    .collect();
+                instrs.push(SymbolicInstr {
+                    opcode: opcode_entity(terminator_to_string(&bb.term)),
+                    ty: type_entity(&bb.term.get_type(types)),
+                    arguments: arguments(terminator_operands(&bb.term)),
+                });
+                instrs
+            })
+            .collect();
+        Self {
+            blocks: function
+                .basic_blocks
+                .iter()
+                .map(|bb| bb.name.to_string())
+                .collect(),
+            flow_order,
+            instrs,
+        }
+    }
+}
+
+fn symbolic_functions(mod_features: &ModFeatures) -> PyResult<&HashMap<String, SymbolicFunction>> {
+    mod_features
+        .symbolic_functions()
+        .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)
+}
+
+fn find_function<'a>(mod_features: &'a ModFeatures, name: &str) -> PyResult<&'a SymbolicFunction> {
+    symbolic_functions(mod_features)?.get(name).ok_or_else(|| {
+        PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+            "function {} is not defined in the module",
+            name
+        ))
+    })
+}
+
+fn add(embedding: &mut [f64], weight: f64, entity: Option<&Vec<f64>>) {
+    if let Some(entity) = entity {
+        for (value, entity_value) in embedding.iter_mut().zip(entity) {
+            *value += weight * entity_value;
+        }
+    }
+}
+
+// LLVM spells vaarg with an underscore
+fn opcode_entity(opcode: &'static str) -> &'static str {
+    if opcode == "vaarg" {
+        "va_arg"
+    } else {
+        opcode
+    }
+}
+
+// lowercased IR2Vec type entity
+fn type_entity(ty: &Type) -> &'static str {
+    match ty {
+        Type::VoidType => "voidty",
+        Type::IntegerType { .. } => "integerty",
+        Type::PointerType { .. } => "pointerty",
+        Type::FPType(FPType::Double) => "doublety",
+        Type::FPType(_) => "floatty",
+        Type::FuncType { .. } => "functionty",
+        Type::VectorType { .. } => "vectorty",
+        Type::ArrayType { .. } => "arrayty",
+        Type::StructType { .. } | Type::NamedStructType { .. } => "structty",
+        Type::LabelType => "labelty",
+        Type::MetadataType => "metadataty",
+        Type::TokenType => "tokenty",
+        _ => "unknownty",
+    }
+}
+
+// lowercased IR2Vec argument entity, `None` for metadata
+fn operand_entity(operand: &Operand) -> Option<&'static str> {
+    match operand {
+        Operand::LocalOperand { ty, .. } => match ty.as_ref() {
+            Type::PointerType { .. } => Some("pointer"),
+            _ => Some("variable"),
+        },
+        Operand::ConstantOperand(constant) => match constant.as_ref() {
+            Constant::GlobalReference { ty, .. } => match ty.as_ref() {
+                Type::FuncType { .. } => Some("function"),
+                _ => Some("pointer"),
+            },
+            _ => Some("constant"),
+        },
+        Operand::MetadataOperand => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{bitcode, module};
+
+    const VOCAB: &str = "
+add:[1.0, 0.0]
+Ret:[0.0, 1.0]
+integerTy:[0.0, 2.0]
+variable:[4.0, 0.0]
+constant:[0.0, 4.0]
+";
+
+    const IR: &str = r#"
+define i32 @f(i32 %x) {
+entry:
+  %a = add i32 %x, 1
+  %b = add i32 %a, %a
+  ret i32 %b
+}
+
+define i32 @g(i32 %n) {
+entry:
+  br label %loop
+loop:
+  %i = phi i32 [ 0, %entry ], [ %next, %loop ]
+  %next = add i32 %i, 1
+  %c = icmp eq i32 %next, %n
+  br i1 %c, label %exit, label %loop
+exit:
+  ret i32 %i
+}
+
+declare i32 @ext()
+"#;
+
+    fn embed(function: &str, flow_aware: bool) -> Vec<Vec<Vec<f64>>> {
+        let module = module(IR);
+        let function = module.get_func_by_name(function).unwrap();
+        let ir2vec = Ir2Vec::parse(VOCAB).unwrap();
+        ir2vec.embed_instructions(&SymbolicFunction::new(function, &module.types), flow_aware)
+    }
+
+    fn assert_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!(
+                (actual - expected).abs() < 1e-9,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn parses_vocabularies() {
+        let ir2vec = Ir2Vec::parse(VOCAB).unwrap();
+        assert_eq!(ir2vec.dimension, 2);
+        assert_eq!(ir2vec.vocab["ret"], [0.0, 1.0]);
+        assert!(Ir2Vec::parse("add:[1.0]\nsub:[1.0, 2.0]").is_err());
+        assert!(Ir2Vec::parse("add 1.0").is_err());
+        assert!(Ir2Vec::parse("").is_err());
+    }
+
+    #[test]
+    fn weighs_opcode_type_and_arguments() {
+        let blocks = embed("f", false);
+        assert_eq!(blocks.len(), 1);
+        // add + 0.5 * integerty + 0.2 * (variable + constant)
+        assert_close(&blocks[0][0], &[1.8, 1.8]);
+        // add + 0.5 * integerty + 0.2 * 2 * variable
+        assert_close(&blocks[0][1], &[2.6, 1.0]);
+        // ret + 0.2 * variable, void has no embedding
+        assert_close(&blocks[0][2], &[0.8, 1.0]);
+    }
+
+    #[test]
+    fn flow_aware_arguments_take_their_definitions_embedding() {
+        let blocks = embed("f", true);
+        assert_close(&blocks[0][0], &[1.8, 1.8]);
+        assert_close(&blocks[0][1], &[1.72, 1.72]);
+        assert_close(&blocks[0][2], &[0.344, 1.344]);
+    }
+
+    #[test]
+    fn values_around_back_edges_keep_their_generic_embedding() {
+        let generic = embed("g", false);
+        let flow_aware = embed("g", true);
+        assert_eq!(
+            flow_aware.iter().map(Vec::len).collect::<Vec<_>>(),
+            [1, 4, 1]
+        );
+        // the phi reads %next before it is embedded, %next reads the phi after
+        assert_close(&flow_aware[1][0], &generic[1][0]);
+        assert!(flow_aware[1][1] != generic[1][1]);
+    }
+
+    #[test]
+    fn keeps_only_defined_functions() {
+        let mod_features = ModFeatures::from_bc(&bitcode(IR), true).unwrap();
+        let symbolic_functions = mod_features.symbolic_functions().unwrap();
+        let mut names: Vec<&String> = symbolic_functions.keys().collect();
+        names.sort();
+        assert_eq!(names, ["f", "g"]);
+        assert_eq!(symbolic_functions["g"].blocks, ["%entry", "%loop", "%exit"]);
+    }
+}
@@ -1,22 +1,77 @@
+mod attributes;
+mod autophase;
 mod bb_features;
+mod call_graph;
+mod call_site;
+mod cfg;
+mod dominators;
 mod fn_features;
 mod inline_features;
+mod inst_count;
+mod ir2vec;
+mod linkage;
 mod llvm_sys_wrapper;
+mod loops;
+mod memory_features;
+mod mlgo;
 mod mod_features;
+mod operands;
+mod pass_options;
+mod program_graph;
+mod remarks;
+#[cfg(test)]
+mod test_utils;
+mod type_features;
 
-use pyo3::{prelude::*, types::PyBytes};
+use pyo3::{prelude::*, types::PyBytes, IntoPyObjectExt};
 
+use autophase::autophase_feature_names;
 use bb_features::BBFeatures;
+use call_graph::CallGraph;
+use call_site::CallSite;
 use fn_features::FnFeatures;
+use inst_count::{inst_count_feature_names, inst_count_norm_feature_names};
+use ir2vec::Ir2Vec;
 use llvm_sys_wrapper::{bitcode_to_ir, run_inline_pass};
+use loops::LoopFeatures;
+use memory_features::MemoryFeatures;
 use mod_features::ModFeatures;
+use pass_options::PassOptions;
+use program_graph::ProgramGraph;
+use remarks::InlineRemark;
 
 use crate::inline_features::extract_inline_features;
 
+/// Runs the inliner over `bc`. With `collect_remarks` the inliner's remarks are returned
+/// alongside the bitcode as a `(bytes, list[InlineRemark])` tuple.
 #[pyfunction]
-fn llvm_inline_pass<'py>(py: Python<'py>, bc: Bound<'_, PyBytes>) -> PyResult<Bound<'py, PyBytes>> {
+#[pyo3(signature = (bc, collect_remarks = false))]
+fn llvm_inline_pass<'py>(
+    py: Python<'py>,
+    bc: Bound<'_, PyBytes>,
+    collect_remarks: bool,
+) -> PyResult<Bound<'py, PyAny>> {
     let bc = bc.as_bytes();
-    let ret = run_inline_pass(bc);
+    let ret = run_inline_pass(bc, collect_remarks);
+    match ret {
+        Ok((bc, _)) if !collect_remarks => Ok(PyBytes::new(py, &bc).into_any()),
+        Ok((bc, remarks)) => (PyBytes::new(py, &bc), remarks).into_bound_py_any(py),
+        Err(e) => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(e)),
+    }
+}
+
+/// Runs a new pass manager pipeline such as `default<O3>` or `function(sroa,instcombine)`
+/// over `bc` and returns the optimized bitcode.
+#[pyfunction]
+#[pyo3(signature = (bc, pipeline, options = None))]
+fn run_passes<'py>(
+    py: Python<'py>,
+    bc: Bound<'_, PyBytes>,
+    pipeline: &str,
+    options: Option<PassOptions>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let bc = bc.as_bytes();
+    let ret = llvm_sys_wrapper::run_passes(bc, pipeline, &options.unwrap_or_default());
     match ret {
         Ok(bc) => Ok(PyBytes::new(py, &bc)),
         Err(e) => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(e)),
@@ -36,10 +91,22 @@ fn bc_to_ir(bc: Bound<PyBytes>) -> PyResult<String> {
 #[pymodule]
 fn llvm_tools(_py: Python, m: Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(llvm_inline_pass, &m)?)?;
+    m.add_function(wrap_pyfunction!(run_passes, &m)?)?;
     m.add_function(wrap_pyfunction!(bc_to_ir, &m)?)?;
     m.add_function(wrap_pyfunction!(extract_inline_features, &m)?)?;
+    m.add_function(wrap_pyfunction!(autophase_feature_names, &m)?)?;
+    m.add_function(wrap_pyfunction!(inst_count_feature_names, &m)?)?;
+    m.add_function(wrap_pyfunction!(inst_count_norm_feature_names, &m)?)?;
     m.add_class::<ModFeatures>()?;
     m.add_class::<FnFeatures>()?;
     m.add_class::<BBFeatures>()?;
+    m.add_class::<LoopFeatures>()?;
+    m.add_class::<MemoryFeatures>()?;
+    m.add_class::<CallSite>()?;
+    m.add_class::<CallGraph>()?;
+    m.add_class::<InlineRemark>()?;
+    m.add_class::<PassOptions>()?;
+    m.add_class::<Ir2Vec>()?;
+    m.add_class::<ProgramGraph>()?;
     Ok(())
 }
@@ -0,0 +1,44 @@
+use llvm_ir::module::{DLLStorageClass, Linkage, UnnamedAddr, Visibility};
+
+use crate::attributes::llvm_spelling;
+
+// lowercased llvm-ir linkage names LLVM spells differently
+const LINKAGE_SPELLINGS: [(&str, &str); 6] = [
+    ("externalweak", "extern_weak"),
+    ("availableexternally", "available_externally"),
+    ("linkonceany", "linkonce"),
+    ("linkonceodr", "linkonce_odr"),
+    ("weakany", "weak"),
+    ("weakodr", "weak_odr"),
+];
+
+/// The linkage as LLVM spells it, such as `internal` or `linkonce_odr`
+pub fn linkage_name(linkage: &Linkage) -> String {
+    llvm_spelling(linkage, &LINKAGE_SPELLINGS)
+}
+
+/// Whether the linkage keeps the symbol out of reach of other modules
+pub fn is_local(linkage: &Linkage) -> bool {
+    matches!(linkage, Linkage::Private | Linkage::Internal)
+}
+
+/// The visibility: `default`, `hidden` or `protected`
+pub fn visibility_name(visibility: &Visibility) -> String {
+    llvm_spelling(visibility, &[])
+}
+
+/// The DLL storage class: `default`, `dllimport` or `dllexport`
+pub fn dll_storage_class_name(dll_storage_class: &DLLStorageClass) -> String {
+    llvm_spelling(
+        dll_storage_class,
+        &[("import", "dllimport"), ("export", "dllexport")],
+    )
+}
+
+/// `unnamed_addr` or `local_unnamed_addr`
+pub fn unnamed_addr_name(unnamed_addr: &UnnamedAddr) -> String {
+    match unnamed_addr {
+        UnnamedAddr::Local => "local_unnamed_addr".to_string(),
+        UnnamedAddr::Global => "unnamed_addr".to_string(),
+    }
+}
@@ -0,0 +1,150 @@
+// The few pieces of LLVM's C++ API that llvm-c leaves out, wrapped for llvm_sys_wrapper.rs
+
+#include "llvm-c/Core.h"
+#include "llvm/Analysis/AssumptionCache.h"
+#include "llvm/Analysis/InlineCost.h"
+#include "llvm/Analysis/InlineModelFeatureMaps.h"
+#include "llvm/Analysis/TargetTransformInfo.h"
+#include "llvm/IR/InstrTypes.h"
+#include "llvm/IR/LLVMContext.h"
+#include "llvm/IR/LLVMRemarkStreamer.h"
+#include "llvm/Passes/PassBuilder.h"
+#include "llvm/Remarks/RemarkStreamer.h"
+#include "llvm/Support/CommandLine.h"
+#include "llvm/Support/Error.h"
+#include "llvm/Support/raw_ostream.h"
+
+#include <cstddef>
+#include <cstdint>
+#include <iterator>
+#include <string>
+
+using namespace llvm;
+
+// The InlineCost feature names in InlineCostFeatureIndex order, as the ML inline advisor
+// names its inputs
+#define FASTIR_FEATURE_NAME(DTYPE, SHAPE, NAME, DOC) #NAME,
+static const char *const InlineCostFeatureNames[] = {
+    INLINE_COST_FEATURE_ITERATOR(FASTIR_FEATURE_NAME)};
+#undef FASTIR_FEATURE_NAME
+static_assert(std::size(InlineCostFeatureNames) ==
+                  static_cast<size_t>(InlineCostFeatureIndex::NumberOfFeatures),
+              "every InlineCost feature has a name");
+
+// The analyses the inline cost model asks for, registered like the inliner's
+struct FastirInlineCostAnalyses {
+  // the registered analyses refer to the pass builder
+  PassBuilder builder;
+  LoopAnalysisManager loops;
+  FunctionAnalysisManager functions;
+  CGSCCAnalysisManager sccs;
+  ModuleAnalysisManager modules;
+
+  FastirInlineCostAnalyses() {
+    builder.registerModuleAnalyses(modules);
+    builder.registerCGSCCAnalyses(sccs);
+    builder.registerFunctionAnalyses(functions);
+    builder.registerLoopAnalyses(loops);
+    builder.crossRegisterProxies(loops, functions, sccs, modules);
+  }
+};
+
+// The optimization remarks a context streamed, serialized as YAML
+struct FastirRemarkBuffer {
+  std::string yaml;
+  raw_string_ostream os{yaml};
+};
+
+extern "C" {
+
+// Streams the remarks of the passes matching the `passes` regex into a new buffer. On
+// failure returns null and points `error` at a message to free with LLVMDisposeMessage.
+FastirRemarkBuffer *fastir_start_remarks(LLVMContextRef ctx, const char *passes,
+                                         char **error) {
+  auto *buffer = new FastirRemarkBuffer();
+  if (Error err = setupLLVMOptimizationRemarks(*unwrap(ctx), buffer->os, passes,
+                                               "yaml", /*RemarksWithHotness=*/false)) {
+    *error = LLVMCreateMessage(toString(std::move(err)).c_str());
+    delete buffer;
+    return nullptr;
+  }
+  return buffer;
+}
+
+// Detaches the streamer from the context and returns the YAML it wrote, owned by the
+// buffer
+const char *fastir_finish_remarks(LLVMContextRef ctx, FastirRemarkBuffer *buffer,
+                                  size_t *len) {
+  LLVMContext &context = *unwrap(ctx);
+  context.setLLVMRemarkStreamer(nullptr);
+  context.setMainRemarkStreamer(nullptr);
+  buffer->os.flush();
+  *len = buffer->yaml.size();
+  return buffer->yaml.data();
+}
+
+void fastir_dispose_remarks(FastirRemarkBuffer *buffer) { delete buffer; }
+
+// Sets the registered command line option `name` as if `-name=value` had been passed. Returns
+// 0 if there is no such option or it rejects the value. The option keeps the value until
+// fastir_reset_option.
+int fastir_set_option(const char *name, const char *value) {
+  StringMap<cl::Option *> &options = cl::getRegisteredOptions();
+  auto option = options.find(name);
+  if (option == options.end())
+    return 0;
+  return !option->second->addOccurrence(0, name, value);
+}
+
+// Puts the registered command line option `name` back to its default, as if never passed
+void fastir_reset_option(const char *name) {
+  StringMap<cl::Option *> &options = cl::getRegisteredOptions();
+  auto option = options.find(name);
+  if (option != options.end())
+    option->second->reset();
+}
+
+size_t fastir_inline_cost_feature_count() { return std::size(InlineCostFeatureNames); }
+
+const char *fastir_inline_cost_feature_name(size_t index) {
+  return InlineCostFeatureNames[index];
+}
+
+// Analyses for fastir_inline_cost, computed on demand and kept until disposed. The module
+// must not change in between.
+FastirInlineCostAnalyses *fastir_create_inline_cost_analyses() {
+  return new FastirInlineCostAnalyses();
+}
+
+void fastir_dispose_inline_cost_analyses(FastirInlineCostAnalyses *analyses) {
+  delete analyses;
+}
+
+// Computes the cost estimate and the fastir_inline_cost_feature_count() InlineCost
+// features of a call to a defined function, the way the ML inline advisor does. Returns 0,
+// leaving `estimate` and `features` untouched, if the call can't be inlined.
+int fastir_inline_cost(FastirInlineCostAnalyses *analyses, LLVMValueRef call,
+                       int64_t *estimate, int64_t *features) {
+  auto *call_base = dyn_cast<CallBase>(unwrap(call));
+  if (!call_base)
+    return 0;
+  Function *callee = call_base->getCalledFunction();
+  if (!callee || callee->isDeclaration())
+    return 0;
+  FunctionAnalysisManager &functions = analyses->functions;
+  TargetTransformInfo &callee_tti = functions.getResult<TargetIRAnalysis>(*callee);
+  auto get_assumption_cache = [&](Function &function) -> AssumptionCache & {
+    return functions.getResult<AssumptionAnalysis>(function);
+  };
+  auto cost_estimate = getInliningCostEstimate(*call_base, callee_tti, get_assumption_cache);
+  if (!cost_estimate)
+    return 0;
+  auto cost_features = getInliningCostFeatures(*call_base, callee_tti, get_assumption_cache);
+  if (!cost_features)
+    return 0;
+  *estimate = *cost_estimate;
+  for (size_t index = 0; index < cost_features->size(); ++index)
+    features[index] = (*cost_features)[index];
+  return 1;
+}
+}
@@ -4,113 +4,278 @@ use llvm_sys::{
     bit_writer::LLVMWriteBitcodeToMemoryBuffer,
     core::*,
     error::{LLVMDisposeErrorMessage, LLVMGetErrorMessage},
+    prelude::{LLVMContextRef, LLVMDiagnosticInfoRef, LLVMModuleRef, LLVMValueRef},
+    remarks::*,
     target::*,
     target_machine::*,
-    transforms::pass_builder::{
-        LLVMCreatePassBuilderOptions, LLVMDisposePassBuilderOptions, LLVMRunPasses,
-    },
+    transforms::pass_builder::{LLVMDisposePassBuilderOptions, LLVMRunPasses},
+    LLVMAttributeFunctionIndex, LLVMDiagnosticSeverity, LLVMOpcode,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{c_char, c_void, CStr, CString},
+    ptr,
+    sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
 };
-use std::{ffi::CString, ptr};
 
-pub fn run_inline_pass(bc: &[u8]) -> Result<Vec<u8>, String> {
-    unsafe {
-        // fresh llvm context & load the bitcode
-        let ctx = LLVMContextCreate();
-        let buf_name = CString::new("input_bc").unwrap();
-        let mbuf = LLVMCreateMemoryBufferWithMemoryRangeCopy(
-            bc.as_ptr() as *const i8,
-            bc.len(),
-            buf_name.as_ptr(),
-        );
-        let mut module = ptr::null_mut();
-        if LLVMParseBitcodeInContext2(ctx, mbuf, &mut module) != 0 {
-            return Err("could not parse bitcode".into());
+use crate::{attributes::MemoryEffects, pass_options::PassOptions, remarks::InlineRemark};
+
+/// Metadata kind used to tag call instructions with their call site id
+const CALL_SITE_MD_KIND: &str = "fastir.call_site";
+
+/// The function attribute holding a function's memory effects
+const MEMORY_ATTR: &str = "memory";
+
+/// Regex of the passes whose remarks are collected
+const REMARK_PASSES: &str = "^inline$";
+
+/// Remarks a context streams as YAML, see `llvm_shim.cpp`
+#[repr(C)]
+struct RemarkBuffer {
+    _private: [u8; 0],
+}
+
+/// The analyses behind the inline cost model, see `llvm_shim.cpp`
+#[repr(C)]
+struct InlineCostAnalyses {
+    _private: [u8; 0],
+}
+
+extern "C" {
+    fn fastir_start_remarks(
+        ctx: LLVMContextRef,
+        passes: *const c_char,
+        error: *mut *mut c_char,
+    ) -> *mut RemarkBuffer;
+    fn fastir_finish_remarks(
+        ctx: LLVMContextRef,
+        buffer: *mut RemarkBuffer,
+        len: *mut usize,
+    ) -> *const c_char;
+    fn fastir_dispose_remarks(buffer: *mut RemarkBuffer);
+    fn fastir_set_option(name: *const c_char, value: *const c_char) -> i32;
+    fn fastir_reset_option(name: *const c_char);
+    fn fastir_inline_cost_feature_count() -> usize;
+    fn fastir_inline_cost_feature_name(index: usize) -> *const c_char;
+    fn fastir_create_inline_cost_analyses() -> *mut InlineCostAnalyses;
+    fn fastir_dispose_inline_cost_analyses(analyses: *mut InlineCostAnalyses);
+    fn fastir_inline_cost(
+        analyses: *mut InlineCostAnalyses,
+        call: LLVMValueRef,
+        estimate: *mut i64,
+        features: *mut i64,
+    ) -> i32;
+}
+
+/// LLVM keeps its command line options in globals: pipeline runs share them under the read
+/// lock and take the write lock to override them
+static COMMAND_LINE_LOCK: RwLock<()> = RwLock::new(());
+
+/// Command line options overridden for one pipeline run, reset to their defaults before
+/// the lock is released
+struct CommandLineOptions {
+    /// The options set so far
+    names: Vec<CString>,
+    _shared: Option<RwLockReadGuard<'static, ()>>,
+    _exclusive: Option<RwLockWriteGuard<'static, ()>>,
+}
+
+impl CommandLineOptions {
+    /// Sets each (name, value) option, waiting for the runs using other values to finish
+    unsafe fn set(options: &[(&str, String)]) -> Result<Self, String> {
+        if options.is_empty() {
+            return Ok(Self {
+                names: vec![],
+                _shared: Some(
+                    COMMAND_LINE_LOCK
+                        .read()
+                        .unwrap_or_else(PoisonError::into_inner),
+                ),
+                _exclusive: None,
+            });
         }
+        let mut scoped = Self {
+            names: vec![],
+            _shared: None,
+            _exclusive: Some(
+                COMMAND_LINE_LOCK
+                    .write()
+                    .unwrap_or_else(PoisonError::into_inner),
+            ),
+        };
+        for (name, value) in options {
+            let name_cstr = CString::new(*name).map_err(|e| e.to_string())?;
+            let value_cstr = CString::new(value.as_str()).map_err(|e| e.to_string())?;
+            if fastir_set_option(name_cstr.as_ptr(), value_cstr.as_ptr()) == 0 {
+                return Err(format!("could not set LLVM option -{}={}", name, value));
+            }
+            scoped.names.push(name_cstr);
+        }
+        Ok(scoped)
+    }
+}
 
-        // sanity-check that the module is valid
-        if LLVMVerifyModule(
-            module,
-            LLVMVerifierFailureAction::LLVMPrintMessageAction,
-            ptr::null_mut(),
-        ) != 0
-        {
-            return Err("input module failed verification".into());
+impl Drop for CommandLineOptions {
+    fn drop(&mut self) {
+        for name in self.names.iter() {
+            unsafe { fastir_reset_option(name.as_ptr()) };
         }
+    }
+}
 
-        // build & run only the inliner with the PassBuilder C-API
-        let pipeline = CString::new("cgscc(inline)").unwrap();
+/// Outcome of running the inliner over a module with tagged call sites
+pub struct TaggedInlineResult {
+    /// caller_name -> ids of the call sites left in it after inlining
+    pub surviving_call_sites: HashMap<String, HashSet<String>>,
+    /// The inliner's remarks in the order they were emitted
+    pub remarks: Vec<InlineRemark>,
+}
 
-        // use null target machine for target-independent inlining pass
-        let pb_opts = LLVMCreatePassBuilderOptions();
+/// What LLVM's inline cost analysis makes of a call before any inlining, as the ML inline
+/// advisor asks it
+pub struct InlineCost {
+    /// The cost estimate, `threshold - free` as MLGO's `cost_estimate`
+    pub estimate: i64,
+    /// The InlineCost features, named by `inline_cost_feature_names()`
+    pub features: Vec<i64>,
+}
 
-        let error_ref = LLVMRunPasses(module, pipeline.as_ptr(), ptr::null_mut(), pb_opts);
-        if !error_ref.is_null() {
-            // get error message from the error reference
-            let error_msg = LLVMGetErrorMessage(error_ref);
-            let error_str = if !error_msg.is_null() {
-                let error_str = std::ffi::CStr::from_ptr(error_msg)
-                    .to_string_lossy()
-                    .into_owned();
-                LLVMDisposeErrorMessage(error_msg);
-                error_str
-            } else {
-                "LLVMRunPasses failed with unknown error".to_string()
-            };
-            return Err(error_str);
-        }
+/// Runs any new pass manager pipeline text (e.g. `default<O3>` or
+/// `function(sroa,instcombine)`) over the bitcode and returns the resulting bitcode
+pub fn run_passes(bc: &[u8], pipeline: &str, options: &PassOptions) -> Result<Vec<u8>, String> {
+    unsafe {
+        let ctx = LLVMContextCreate();
+        let module = match parse_module(ctx, bc) {
+            Ok(module) => module,
+            Err(e) => {
+                LLVMContextDispose(ctx);
+                return Err(e);
+            }
+        };
+
+        let ret =
+            run_pipeline(ctx, module, pipeline, options, false).map(|_| write_bitcode(module));
+
+        LLVMDisposeModule(module);
+        LLVMContextDispose(ctx);
+
+        ret
+    }
+}
+
+/// Runs the inliner, returning the new bitcode and, if `collect_remarks`, the inliner's remarks
+pub fn run_inline_pass(
+    bc: &[u8],
+    collect_remarks: bool,
+) -> Result<(Vec<u8>, Vec<InlineRemark>), String> {
+    unsafe {
+        // fresh llvm context & load the bitcode
+        let ctx = LLVMContextCreate();
+        let module = match parse_module(ctx, bc) {
+            Ok(module) => module,
+            Err(e) => {
+                LLVMContextDispose(ctx);
+                return Err(e);
+            }
+        };
 
-        // re-serialize as bitcode
-        let bc_buf = LLVMWriteBitcodeToMemoryBuffer(module);
-        let start = LLVMGetBufferStart(bc_buf) as *const u8;
-        let len = LLVMGetBufferSize(bc_buf);
-        let ret = std::slice::from_raw_parts(start, len).to_vec();
+        // build & run only the inliner with the PassBuilder C-API
+        let ret = run_pipeline(
+            ctx,
+            module,
+            "cgscc(inline)",
+            &PassOptions::default(),
+            collect_remarks,
+        )
+        .map(|remarks| (write_bitcode(module), remarks));
 
         // clean up
-        LLVMDisposePassBuilderOptions(pb_opts);
-        LLVMDisposeMemoryBuffer(mbuf);
         LLVMDisposeModule(module);
         LLVMContextDispose(ctx);
 
-        Ok(ret)
+        ret
     }
 }
 
-pub fn bitcode_to_ir(bc: &[u8]) -> Result<String, String> {
+/// Tags every call, invoke and callbr with its call site id (`caller:bb_index:instr_index`),
+/// runs `pipeline` and returns the ids still present in each function afterwards along
+/// with the inliner's remarks.
+///
+/// Inlining erases the call it inlines while cloned calls keep their tag, so with the inliner
+/// alone a call site was inlined exactly when its id is missing from its caller. Full
+/// pipelines also delete calls for other reasons, the remarks tell which were inlined.
+pub fn run_tagged_inline_pass(
+    bc: &[u8],
+    pipeline: &str,
+    options: &PassOptions,
+) -> Result<TaggedInlineResult, String> {
     unsafe {
-        // create fresh llvm context & load the bitcode
         let ctx = LLVMContextCreate();
-        let buf_name = CString::new("input_bc").unwrap();
-        let mbuf = LLVMCreateMemoryBufferWithMemoryRangeCopy(
-            bc.as_ptr() as *const i8,
-            bc.len(),
-            buf_name.as_ptr(),
+        let module = match parse_module(ctx, bc) {
+            Ok(module) => module,
+            Err(e) => {
+                LLVMContextDispose(ctx);
+                return Err(e);
+            }
+        };
+
+        let kind_name = CALL_SITE_MD_KIND.as_bytes();
+        let md_kind = LLVMGetMDKindIDInContext(
+            ctx,
+            kind_name.as_ptr() as *const c_char,
+            kind_name.len() as u32,
         );
 
-        let mut module = ptr::null_mut();
-        if LLVMParseBitcodeInContext2(ctx, mbuf, &mut module) != 0 {
-            LLVMDisposeMemoryBuffer(mbuf);
-            LLVMContextDispose(ctx);
-            return Err("could not parse bitcode".into());
+        for (function, instrs) in call_instructions(module) {
+            for (bb_index, instr_index, instr) in instrs {
+                let id = format!("{}:{}:{}", function, bb_index, instr_index);
+                let md_str = LLVMMDStringInContext2(ctx, id.as_ptr() as *const c_char, id.len());
+                let mut operands = [md_str];
+                let node = LLVMMDNodeInContext2(ctx, operands.as_mut_ptr(), operands.len());
+                LLVMSetMetadata(instr, md_kind, LLVMMetadataAsValue(ctx, node));
+            }
         }
 
-        // sanity check that the module is valid
-        if LLVMVerifyModule(
-            module,
-            LLVMVerifierFailureAction::LLVMPrintMessageAction,
-            ptr::null_mut(),
-        ) != 0
-        {
-            LLVMDisposeModule(module);
-            LLVMDisposeMemoryBuffer(mbuf);
-            LLVMContextDispose(ctx);
-            return Err("input module failed verification".into());
-        }
+        let ret = run_pipeline(ctx, module, pipeline, options, true).map(|remarks| {
+            let surviving_call_sites = call_instructions(module)
+                .into_iter()
+                .map(|(function, instrs)| {
+                    let ids = instrs
+                        .into_iter()
+                        .filter_map(|(_, _, instr)| call_site_tag(instr, md_kind))
+                        .collect();
+                    (function, ids)
+                })
+                .collect();
+            TaggedInlineResult {
+                surviving_call_sites,
+                remarks,
+            }
+        });
+
+        LLVMDisposeModule(module);
+        LLVMContextDispose(ctx);
+
+        ret
+    }
+}
+
+pub fn bitcode_to_ir(bc: &[u8]) -> Result<String, String> {
+    unsafe {
+        // create fresh llvm context & load the bitcode
+        let ctx = LLVMContextCreate();
+        let module = match parse_module(ctx, bc) {
+            Ok(module) => module,
+            Err(e) => {
+                LLVMContextDispose(ctx);
+                return Err(e);
+            }
+        };
 
         // convert module to LLVM IR string
         let ir_cstr = LLVMPrintModuleToString(module);
         if ir_cstr.is_null() {
             LLVMDisposeModule(module);
-            LLVMDisposeMemoryBuffer(mbuf);
             LLVMContextDispose(ctx);
             return Err("failed to convert module to string".into());
         }
@@ -122,9 +287,407 @@ pub fn bitcode_to_ir(bc: &[u8]) -> Result<String, String> {
         // clean up
         LLVMDisposeMessage(ir_cstr);
         LLVMDisposeModule(module);
-        LLVMDisposeMemoryBuffer(mbuf);
         LLVMContextDispose(ctx);
 
         Ok(ir_string)
     }
 }
+
+/// The memory effects of every function with a `memory(...)` attribute, which llvm-ir
+/// does not decode
+pub fn memory_effects(bc: &[u8]) -> Result<HashMap<String, MemoryEffects>, String> {
+    unsafe {
+        let ctx = LLVMContextCreate();
+        let module = match parse_module(ctx, bc) {
+            Ok(module) => module,
+            Err(e) => {
+                LLVMContextDispose(ctx);
+                return Err(e);
+            }
+        };
+
+        let kind = LLVMGetEnumAttributeKindForName(
+            MEMORY_ATTR.as_ptr() as *const c_char,
+            MEMORY_ATTR.len(),
+        );
+        let mut effects = HashMap::new();
+        let mut function = LLVMGetFirstFunction(module);
+        while !function.is_null() {
+            let attr = LLVMGetEnumAttributeAtIndex(function, LLVMAttributeFunctionIndex, kind);
+            if !attr.is_null() {
+                effects.insert(
+                    value_name(function),
+                    MemoryEffects::from_int(LLVMGetEnumAttributeValue(attr)),
+                );
+            }
+            function = LLVMGetNextFunction(function);
+        }
+
+        LLVMDisposeModule(module);
+        LLVMContextDispose(ctx);
+
+        Ok(effects)
+    }
+}
+
+/// The names of the InlineCost features of the linked LLVM, in the order `InlineCost`
+/// holds them
+pub fn inline_cost_feature_names() -> Vec<String> {
+    unsafe {
+        (0..fastir_inline_cost_feature_count())
+            .map(|index| {
+                CStr::from_ptr(fastir_inline_cost_feature_name(index))
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+}
+
+/// The inline cost of every call to a defined function by call site id
+/// (`caller:bb_index:instr_index`), leaving out the calls that can't be inlined
+pub fn inline_costs(bc: &[u8]) -> Result<HashMap<String, InlineCost>, String> {
+    unsafe {
+        let ctx = LLVMContextCreate();
+        let module = match parse_module(ctx, bc) {
+            Ok(module) => module,
+            Err(e) => {
+                LLVMContextDispose(ctx);
+                return Err(e);
+            }
+        };
+
+        let analyses = fastir_create_inline_cost_analyses();
+        let mut costs = HashMap::new();
+        for (function, instrs) in call_instructions(module) {
+            for (bb_index, instr_index, instr) in instrs {
+                let mut estimate = 0;
+                let mut features = vec![0; fastir_inline_cost_feature_count()];
+                if fastir_inline_cost(analyses, instr, &mut estimate, features.as_mut_ptr()) != 0 {
+                    let id = format!("{}:{}:{}", function, bb_index, instr_index);
+                    costs.insert(id, InlineCost { estimate, features });
+                }
+            }
+        }
+
+        // the analyses refer to the module
+        fastir_dispose_inline_cost_analyses(analyses);
+        LLVMDisposeModule(module);
+        LLVMContextDispose(ctx);
+
+        Ok(costs)
+    }
+}
+
+/// Parses bitcode into `ctx` and verifies the resulting module
+unsafe fn parse_module(ctx: LLVMContextRef, bc: &[u8]) -> Result<LLVMModuleRef, String> {
+    let buf_name = CString::new("input_bc").unwrap();
+    let mbuf = LLVMCreateMemoryBufferWithMemoryRangeCopy(
+        bc.as_ptr() as *const c_char,
+        bc.len(),
+        buf_name.as_ptr(),
+    );
+
+    // the module is fully materialized, so the buffer can go right away
+    let mut module = ptr::null_mut();
+    let parse_failed = LLVMParseBitcodeInContext2(ctx, mbuf, &mut module) != 0;
+    LLVMDisposeMemoryBuffer(mbuf);
+    if parse_failed {
+        return Err("could not parse bitcode".into());
+    }
+
+    // sanity-check that the module is valid
+    if LLVMVerifyModule(
+        module,
+        LLVMVerifierFailureAction::LLVMPrintMessageAction,
+        ptr::null_mut(),
+    ) != 0
+    {
+        LLVMDisposeModule(module);
+        return Err("input module failed verification".into());
+    }
+
+    Ok(module)
+}
+
+/// Diagnostic handler collecting the errors reported while passes run into the
+/// `Vec<String>` behind `context`. Remarks reach the remark streamer instead and warnings
+/// are dropped.
+extern "C" fn collect_error(info: LLVMDiagnosticInfoRef, context: *mut c_void) {
+    unsafe {
+        if matches!(
+            LLVMGetDiagInfoSeverity(info),
+            LLVMDiagnosticSeverity::LLVMDSError
+        ) {
+            let description = LLVMGetDiagInfoDescription(info);
+            let errors = &mut *(context as *mut Vec<String>);
+            errors.push(CStr::from_ptr(description).to_string_lossy().into_owned());
+            LLVMDisposeMessage(description);
+        }
+    }
+}
+
+/// Runs a new pass manager pipeline over `module` in place, returning the inliner remarks
+/// emitted along the way if `collect_remarks`
+unsafe fn run_pipeline(
+    ctx: LLVMContextRef,
+    module: LLVMModuleRef,
+    pipeline: &str,
+    options: &PassOptions,
+    collect_remarks: bool,
+) -> Result<Vec<InlineRemark>, String> {
+    let pipeline = CString::new(pipeline).map_err(|e| e.to_string())?;
+    // held until the pipeline is done
+    let _command_line = CommandLineOptions::set(&options.command_line_options())?;
+
+    // the remarks are streamed to a buffer owned by the context until finished below
+    let remark_buffer = if collect_remarks {
+        let passes = CString::new(REMARK_PASSES).unwrap();
+        let mut error = ptr::null_mut();
+        let buffer = fastir_start_remarks(ctx, passes.as_ptr(), &mut error);
+        if buffer.is_null() {
+            let error_str = CStr::from_ptr(error).to_string_lossy().into_owned();
+            LLVMDisposeMessage(error);
+            return Err(error_str);
+        }
+        buffer
+    } else {
+        ptr::null_mut()
+    };
+
+    // the handler writes through this pointer until it is unset below
+    let mut errors: Vec<String> = vec![];
+    LLVMContextSetDiagnosticHandler(
+        ctx,
+        Some(collect_error),
+        &mut errors as *mut Vec<String> as *mut c_void,
+    );
+
+    // use null target machine for target-independent passes
+    let pb_opts = options.to_llvm();
+
+    let error_ref = LLVMRunPasses(module, pipeline.as_ptr(), ptr::null_mut(), pb_opts);
+    LLVMDisposePassBuilderOptions(pb_opts);
+    LLVMContextSetDiagnosticHandler(ctx, None, ptr::null_mut());
+
+    let remarks = if remark_buffer.is_null() {
+        Ok(vec![])
+    } else {
+        let mut len = 0;
+        let yaml = fastir_finish_remarks(ctx, remark_buffer, &mut len);
+        let remarks = parse_inline_remarks(std::slice::from_raw_parts(yaml as *const u8, len));
+        fastir_dispose_remarks(remark_buffer);
+        remarks
+    };
+
+    if !error_ref.is_null() {
+        // get error message from the error reference
+        let error_msg = LLVMGetErrorMessage(error_ref);
+        let error_str = if !error_msg.is_null() {
+            let error_str = std::ffi::CStr::from_ptr(error_msg)
+                .to_string_lossy()
+                .into_owned();
+            LLVMDisposeErrorMessage(error_msg);
+            error_str
+        } else {
+            "LLVMRunPasses failed with unknown error".to_string()
+        };
+        return Err(error_str);
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    remarks
+}
+
+/// Reads the inliner's remarks out of a YAML remark stream, in the order they were emitted
+unsafe fn parse_inline_remarks(yaml: &[u8]) -> Result<Vec<InlineRemark>, String> {
+    if yaml.is_empty() {
+        return Ok(vec![]);
+    }
+    let parser = LLVMRemarkParserCreateYAML(yaml.as_ptr() as *const c_void, yaml.len() as u64);
+    let mut remarks = vec![];
+    loop {
+        let entry = LLVMRemarkParserGetNext(parser);
+        if entry.is_null() {
+            break;
+        }
+        if remark_string(LLVMRemarkEntryGetPassName(entry)) == "inline" {
+            let mut args = vec![];
+            let mut arg = LLVMRemarkEntryGetFirstArg(entry);
+            while !arg.is_null() {
+                args.push((
+                    remark_string(LLVMRemarkArgGetKey(arg)),
+                    remark_string(LLVMRemarkArgGetValue(arg)),
+                ));
+                arg = LLVMRemarkEntryGetNextArg(arg, entry);
+            }
+            let name = remark_string(LLVMRemarkEntryGetRemarkName(entry));
+            remarks.extend(InlineRemark::new(&name, &args));
+        }
+        LLVMRemarkEntryDispose(entry);
+    }
+    let error = (LLVMRemarkParserHasError(parser) != 0).then(|| {
+        CStr::from_ptr(LLVMRemarkParserGetErrorMessage(parser))
+            .to_string_lossy()
+            .into_owned()
+    });
+    LLVMRemarkParserDispose(parser);
+    match error {
+        Some(error) => Err(error),
+        None => Ok(remarks),
+    }
+}
+
+/// Copies a string of the remark parser, which are not null-terminated
+unsafe fn remark_string(string: LLVMRemarkStringRef) -> String {
+    let data = LLVMRemarkStringGetData(string) as *const u8;
+    let bytes = std::slice::from_raw_parts(data, LLVMRemarkStringGetLen(string) as usize);
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Serializes `module` as bitcode
+pub unsafe fn write_bitcode(module: LLVMModuleRef) -> Vec<u8> {
+    let bc_buf = LLVMWriteBitcodeToMemoryBuffer(module);
+    let start = LLVMGetBufferStart(bc_buf) as *const u8;
+    let len = LLVMGetBufferSize(bc_buf);
+    let ret = std::slice::from_raw_parts(start, len).to_vec();
+    LLVMDisposeMemoryBuffer(bc_buf);
+    ret
+}
+
+unsafe fn value_name(value: LLVMValueRef) -> String {
+    let mut len = 0;
+    let name = LLVMGetValueName2(value, &mut len);
+    let bytes = std::slice::from_raw_parts(name as *const u8, len);
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// Every call-like instruction of each defined function as (bb_index, instr_index, instr),
+/// numbered the same way `llvm_ir` numbers them
+unsafe fn call_instructions(
+    module: LLVMModuleRef,
+) -> Vec<(String, Vec<(usize, usize, LLVMValueRef)>)> {
+    let mut functions = vec![];
+    let mut function = LLVMGetFirstFunction(module);
+    while !function.is_null() {
+        if LLVMIsDeclaration(function) == 0 {
+            let mut calls = vec![];
+            let mut bb = LLVMGetFirstBasicBlock(function);
+            let mut bb_index = 0;
+            while !bb.is_null() {
+                let mut instr = LLVMGetFirstInstruction(bb);
+                let mut instr_index = 0;
+                while !instr.is_null() {
+                    if matches!(
+                        LLVMGetInstructionOpcode(instr),
+                        LLVMOpcode::LLVMCall | LLVMOpcode::LLVMInvoke | LLVMOpcode::LLVMCallBr
+                    ) {
+                        calls.push((bb_index, instr_index, instr));
+                    }
+                    instr = LLVMGetNextInstruction(instr);
+                    instr_index += 1;
+                }
+                bb = LLVMGetNextBasicBlock(bb);
+                bb_index += 1;
+            }
+            functions.push((value_name(function), calls));
+        }
+        function = LLVMGetNextFunction(function);
+    }
+    functions
+}
+
+/// The call site id attached to `instr`, if any
+unsafe fn call_site_tag(instr: LLVMValueRef, md_kind: u32) -> Option<String> {
+    let node = LLVMGetMetadata(instr, md_kind);
+    if node.is_null() || LLVMGetMDNodeNumOperands(node) != 1 {
+        return None;
+    }
+    let mut operand = ptr::null_mut();
+    LLVMGetMDNodeOperands(node, &mut operand);
+    let mut len = 0;
+    let tag = LLVMGetMDString(operand, &mut len);
+    if tag.is_null() {
+        return None;
+    }
+    let bytes = std::slice::from_raw_parts(tag as *const u8, len as usize);
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::bitcode;
+
+    const IR: &str = r#"
+define internal i32 @leaf(i32 %x) {
+entry:
+  %y = add i32 %x, 1
+  ret i32 %y
+}
+
+@sink = global i32 0
+
+define i32 @big(i32 %x) noinline {
+entry:
+  store i32 %x, ptr @sink
+  ret i32 %x
+}
+
+define i32 @main(i32 %x) {
+entry:
+  %a = call i32 @leaf(i32 %x)
+  %b = call i32 @big(i32 %a)
+  ret i32 %b
+}
+"#;
+
+    #[test]
+    fn collects_structured_inline_remarks() {
+        let (_, remarks) = run_inline_pass(&bitcode(IR), true).unwrap();
+        assert_eq!(remarks.len(), 2);
+
+        let inlined = &remarks[0];
+        assert_eq!(inlined.kind, "Inlined");
+        assert_eq!(
+            (inlined.caller.as_str(), inlined.callee.as_str()),
+            ("main", "leaf")
+        );
+        assert!(inlined.cost.is_some() && inlined.threshold.is_some());
+        assert!(inlined.message.starts_with("'leaf' inlined into 'main'"));
+
+        let never = &remarks[1];
+        assert_eq!(never.kind, "NeverInline");
+        assert_eq!((never.cost, never.threshold), (None, None));
+        assert_eq!(never.reason.as_deref(), Some("noinline function attribute"));
+    }
+
+    #[test]
+    fn remarks_are_only_collected_on_request() {
+        let (_, remarks) = run_inline_pass(&bitcode(IR), false).unwrap();
+        assert!(remarks.is_empty());
+        // nothing is left switched on for later runs
+        let (_, remarks) = run_inline_pass(&bitcode(IR), true).unwrap();
+        assert_eq!(remarks.len(), 2);
+    }
+
+    #[test]
+    fn reports_invalid_pipelines() {
+        let error = run_passes(&bitcode(IR), "no-such-pass", &PassOptions::default());
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn runs_pipelines_with_options() {
+        let options = PassOptions {
+            verify_each: Some(true),
+            ..PassOptions::default()
+        };
+        let bc = run_passes(&bitcode(IR), "default<O2>", &options).unwrap();
+        let ir = bitcode_to_ir(&bc).unwrap();
+        // @leaf is inlined into its only caller and then deleted
+        assert!(!ir.contains("@leaf"));
+        assert!(ir.contains("call i32 @big"));
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::HashMap;
+
+use llvm_ir::{Constant, Function, Instruction, IntPredicate, Name, Operand, Terminator};
+use pyo3::pyclass;
+
+use crate::{cfg::Cfg, dominators::dominates};
+
+/// A natural loop, every back edge into the same header merged
+pub struct NaturalLoop {
+    /// The block every entry into the loop goes through
+    pub header: usize,
+    /// The blocks with a back edge to the header
+    pub latches: Vec<usize>,
+    /// The blocks of the loop in function order, header included
+    pub blocks: Vec<usize>,
+}
+
+/// The natural loops of a function in header order. A back edge is an edge whose
+/// target dominates its source; loops in irreducible regions are not found.
+pub fn natural_loops(cfg: &Cfg, idom: &[Option<usize>]) -> Vec<NaturalLoop> {
+    let mut loops = Vec::new();
+    for header in 0..cfg.block_count() {
+        let latches: Vec<usize> = cfg.preds[header]
+            .iter()
+            .copied()
+            .filter(|&pred| dominates(idom, header, pred))
+            .collect();
+        if latches.is_empty() {
+            continue;
+        }
+
+        // everything reaching a latch without passing through the header
+        let mut in_loop = vec![false; cfg.block_count()];
+        in_loop[header] = true;
+        let mut stack = latches.clone();
+        while let Some(bb) = stack.pop() {
+            if !in_loop[bb] {
+                in_loop[bb] = true;
+                stack.extend(
+                    cfg.preds[bb]
+                        .iter()
+                        .copied()
+                        .filter(|&pred| dominates(idom, header, pred)),
+                );
+            }
+        }
+        let blocks = (0..cfg.block_count()).filter(|&bb| in_loop[bb]).collect();
+        loops.push(NaturalLoop {
+            header,
+            latches,
+            blocks,
+        });
+    }
+    loops
+}
+
+/// The number of loops containing each block, 0 outside of loops
+pub fn loop_depths(block_count: usize, loops: &[NaturalLoop]) -> Vec<usize> {
+    let mut depths = vec![0; block_count];
+    for natural_loop in loops {
+        for &bb in natural_loop.blocks.iter() {
+            depths[bb] += 1;
+        }
+    }
+    depths
+}
+
+#[pyclass]
+#[derive(Clone)]
+/// Features of a natural loop
+pub struct LoopFeatures {
+    #[pyo3(get)]
+    /// The name of the loop's header block
+    pub header: String,
+    #[pyo3(get)]
+    /// The number of blocks in the loop, nested loops included
+    pub block_count: u64,
+    #[pyo3(get)]
+    /// The number of blocks with a back edge to the header
+    pub latch_count: u64,
+    #[pyo3(get)]
+    /// The number of blocks in the loop branching out of it
+    pub exiting_block_count: u64,
+    #[pyo3(get)]
+    /// The number of blocks outside the loop branched to from inside it
+    pub exit_block_count: u64,
+    #[pyo3(get)]
+    /// The number of loops containing this one, itself included
+    pub depth: u64,
+    #[pyo3(get)]
+    /// How many times the header runs, when the loop is controlled by an integer
+    /// induction variable with constant start, step and bound
+    pub trip_count: Option<u64>,
+}
+
+impl LoopFeatures {
+    pub fn new(
+        function: &Function,
+        cfg: &Cfg,
+        natural_loop: &NaturalLoop,
+        depths: &[usize],
+    ) -> Self {
+        let mut exiting_blocks = Vec::new();
+        let mut exit_blocks = Vec::new();
+        for &bb in natural_loop.blocks.iter() {
+            for &succ in cfg.succs[bb].iter() {
+                if natural_loop.blocks.contains(&succ) {
+                    continue;
+                }
+                if !exiting_blocks.contains(&bb) {
+                    exiting_blocks.push(bb);
+                }
+                if !exit_blocks.contains(&succ) {
+                    exit_blocks.push(succ);
+                }
+            }
+        }
+        let trip_count = match exiting_blocks[..] {
+            [exiting] => constant_trip_count(function, cfg, natural_loop, exiting),
+            _ => None,
+        };
+        Self {
+            header: cfg.names[natural_loop.header].clone(),
+            block_count: natural_loop.blocks.len() as u64,
+            latch_count: natural_loop.latches.len() as u64,
+            exiting_block_count: exiting_blocks.len() as u64,
+            exit_block_count: exit_blocks.len() as u64,
+            depth: depths[natural_loop.header] as u64,
+            trip_count,
+        }
+    }
+}
+
+// Finds the trip count of a loop leaving through a single block whose induction
+// variable is a header phi starting at a constant and stepped by a constant `add` or
+// `sub` every iteration, compared against a constant to leave the loop.
+fn constant_trip_count(
+    function: &Function,
+    cfg: &Cfg,
+    natural_loop: &NaturalLoop,
+    exiting: usize,
+) -> Option<u64> {
+    // the exit test must run on every iteration
+    if exiting != natural_loop.header && natural_loop.latches[..] != [exiting] {
+        return None;
+    }
+    let position = |name: &Name| cfg.names.iter().position(|bb| *bb == name.to_string());
+    let definitions: HashMap<&Name, &Instruction> = natural_loop
+        .blocks
+        .iter()
+        .flat_map(|&bb| function.basic_blocks[bb].instrs.iter())
+        .filter_map(|instr| instr.try_get_result().map(|result| (result, instr)))
+        .collect();
+
+    let Terminator::CondBr(condbr) = &function.basic_blocks[exiting].term else {
+        return None;
+    };
+    let stays_if_true = natural_loop.blocks.contains(&position(&condbr.true_dest)?);
+    let Operand::LocalOperand { name, .. } = &condbr.condition else {
+        return None;
+    };
+    let Some(Instruction::ICmp(icmp)) = definitions.get(name) else {
+        return None;
+    };
+    let (predicate, compared, (bits, bound)) =
+        match (int_constant(&icmp.operand0), int_constant(&icmp.operand1)) {
+            (None, Some(bound)) => (icmp.predicate, &icmp.operand0, bound),
+            (Some(bound), None) => (swapped(icmp.predicate), &icmp.operand1, bound),
+            _ => return None,
+        };
+    let Operand::LocalOperand { name: compared, .. } = compared else {
+        return None;
+    };
+
+    // the loop may compare the phi itself or its next value
+    let (phi_name, compared_step) = match definitions.get(compared)? {
+        Instruction::Phi(_) => (compared, None),
+        instr => {
+            let (base, step) = increment(instr)?;
+            (base, Some(step))
+        }
+    };
+    let Some(Instruction::Phi(phi)) = definitions.get(phi_name) else {
+        return None;
+    };
+    let in_header = function.basic_blocks[natural_loop.header]
+        .instrs
+        .iter()
+        .any(|instr| instr.try_get_result() == Some(phi_name));
+    if !in_header {
+        return None;
+    }
+    let mut start = None;
+    let mut step = None;
+    for (value, pred) in phi.incoming_values.iter() {
+        if natural_loop.blocks.contains(&position(pred)?) {
+            let Operand::LocalOperand { name, .. } = value else {
+                return None;
+            };
+            let (base, by) = increment(definitions.get(name)?)?;
+            if base != phi_name || step.is_some_and(|step| step != by) {
+                return None;
+            }
+            step = Some(by);
+        } else {
+            let (_, value) = int_constant(value)?;
+            if start.is_some_and(|start| start != value) {
+                return None;
+            }
+            start = Some(value);
+        }
+    }
+    let (start, step) = (start?, step?);
+    if compared_step.is_some_and(|compared_step| compared_step != step) {
+        return None;
+    }
+
+    // the compared value runs through first, first + step, ... until the exit test holds
+    let exit = if stays_if_true {
+        inverse(predicate)
+    } else {
+        predicate
+    };
+    let first = start.wrapping_add(compared_step.unwrap_or(0));
+    trip_count(exit, first, step, bound, bits)
+}
+
+// How many times the exit test runs until `exit` holds for `first + k * step`, wrapping
+// at `bits`, in closed form as ScalarEvolution computes it. `None` if it never holds or
+// the answer would need following the value around the wrap more than once.
+fn trip_count(exit: IntPredicate, first: u64, step: u64, bound: u64, bits: u32) -> Option<u64> {
+    if bits == 0 || bits > 64 {
+        return None;
+    }
+    let mask = u64::MAX >> (64 - bits);
+    let (first, step, bound) = (first & mask, step & mask, bound & mask);
+    let iterations = if compare(exit, first, bound, bits) {
+        0
+    } else if step == 0 {
+        return None;
+    } else {
+        match exit {
+            IntPredicate::EQ => iterations_to_reach(bound.wrapping_sub(first) & mask, step, mask)?,
+            // the value starts at the bound and any step moves it off
+            IntPredicate::NE => 1,
+            _ => iterations_to_leave(exit, first, step, bound, bits)?,
+        }
+    };
+    iterations.checked_add(1)
+}
+
+// the smallest k with k * step == distance modulo mask + 1: a power of two divides the
+// step, the rest is inverted
+fn iterations_to_reach(distance: u64, step: u64, mask: u64) -> Option<u64> {
+    let zeros = step.trailing_zeros();
+    if distance & ((1 << zeros) - 1) != 0 {
+        return None;
+    }
+    Some((distance >> zeros).wrapping_mul(odd_inverse(step >> zeros)) & (mask >> zeros))
+}
+
+// the inverse of an odd number modulo 2^64 by Newton's iteration, each doubling the
+// correct low bits from the 3 an odd number is its own inverse to
+fn odd_inverse(odd: u64) -> u64 {
+    let mut inverse = odd;
+    for _ in 0..5 {
+        inverse = inverse.wrapping_mul(2u64.wrapping_sub(odd.wrapping_mul(inverse)));
+    }
+    inverse
+}
+
+// The smallest k taking `first + k * step` out of the interval of values failing a
+// relational exit test, `first` in it. Signed tests become unsigned ones by flipping
+// the sign bit, which commutes with the wrapping step. The value is followed both ways
+// round, the step being an increment or a decrement, and must land in the exit
+// interval without jumping over it.
+fn iterations_to_leave(
+    exit: IntPredicate,
+    first: u64,
+    step: u64,
+    bound: u64,
+    bits: u32,
+) -> Option<u64> {
+    let mask = u64::MAX >> (64 - bits);
+    let sign = 1 << (bits - 1);
+    let (exit, first, bound) = match exit {
+        IntPredicate::SLT => (IntPredicate::ULT, first ^ sign, bound ^ sign),
+        IntPredicate::SLE => (IntPredicate::ULE, first ^ sign, bound ^ sign),
+        IntPredicate::SGT => (IntPredicate::UGT, first ^ sign, bound ^ sign),
+        IntPredicate::SGE => (IntPredicate::UGE, first ^ sign, bound ^ sign),
+        _ => (exit, first, bound),
+    };
+    // the values staying in the loop, which hold `first` so aren't empty
+    let (low, high) = match exit {
+        IntPredicate::ULT => (bound, mask),
+        IntPredicate::ULE => (bound.checked_add(1)?, mask),
+        IntPredicate::UGT => (0, bound),
+        IntPredicate::UGE => (0, bound.checked_sub(1)?),
+        _ => return None,
+    };
+    let exit_len = mask - (high - low);
+    let leave = |distance: u64, step: u64| {
+        // how far past the staying values the value lands
+        let overshoot = step - distance % step;
+        (overshoot <= exit_len).then(|| (distance / step).checked_add(1))
+    };
+    leave(high - first, step)
+        .or_else(|| leave(first - low, step.wrapping_neg() & mask))
+        .flatten()
+}
+
+// the predicate holding exactly when this one doesn't
+fn inverse(predicate: IntPredicate) -> IntPredicate {
+    match predicate {
+        IntPredicate::EQ => IntPredicate::NE,
+        IntPredicate::NE => IntPredicate::EQ,
+        IntPredicate::UGT => IntPredicate::ULE,
+        IntPredicate::UGE => IntPredicate::ULT,
+        IntPredicate::ULT => IntPredicate::UGE,
+        IntPredicate::ULE => IntPredicate::UGT,
+        IntPredicate::SGT => IntPredicate::SLE,
+        IntPredicate::SGE => IntPredicate::SLT,
+        IntPredicate::SLT => IntPredicate::SGE,
+        IntPredicate::SLE => IntPredicate::SGT,
+    }
+}
+
+// the phi an `add` or `sub` of a constant steps, and the step
+fn increment(instruction: &Instruction) -> Option<(&Name, u64)> {
+    match instruction {
+        Instruction::Add(add) => match (&add.operand0, &add.operand1) {
+            (Operand::LocalOperand { name, .. }, step)
+            | (step, Operand::LocalOperand { name, .. }) => Some((name, int_constant(step)?.1)),
+            _ => None,
+        },
+        Instruction::Sub(sub) => match &sub.operand0 {
+            Operand::LocalOperand { name, .. } => {
+                Some((name, int_constant(&sub.operand1)?.1.wrapping_neg()))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// the width and value of an integer constant operand
+fn int_constant(operand: &Operand) -> Option<(u32, u64)> {
+    match operand {
+        Operand::ConstantOperand(constant) => match constant.as_ref() {
+            Constant::Int { bits, value } => Some((*bits, *value)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// the predicate testing the same thing with its operands swapped
+fn swapped(predicate: IntPredicate) -> IntPredicate {
+    match predicate {
+        IntPredicate::UGT => IntPredicate::ULT,
+        IntPredicate::UGE => IntPredicate::ULE,
+        IntPredicate::ULT => IntPredicate::UGT,
+        IntPredicate::ULE => IntPredicate::UGE,
+        IntPredicate::SGT => IntPredicate::SLT,
+        IntPredicate::SGE => IntPredicate::SLE,
+        IntPredicate::SLT => IntPredicate::SGT,
+        IntPredicate::SLE => IntPredicate::SGE,
+        IntPredicate::EQ | IntPredicate::NE => predicate,
+    }
+}
+
+fn compare(predicate: IntPredicate, lhs: u64, rhs: u64, bits: u32) -> bool {
+    let signed = |value: u64| {
+        if bits >= 64 {
+            value as i64
+        } else {
+            ((value << (64 - bits)) as i64) >> (64 - bits)
+        }
+    };
+    match predicate {
+        IntPredicate::EQ => lhs == rhs,
+        IntPredicate::NE => lhs != rhs,
+        IntPredicate::UGT => lhs > rhs,
+        IntPredicate::UGE => lhs >= rhs,
+        IntPredicate::ULT => lhs < rhs,
+        IntPredicate::ULE => lhs <= rhs,
+        IntPredicate::SGT => signed(lhs) > signed(rhs),
+        IntPredicate::SGE => signed(lhs) >= signed(rhs),
+        IntPredicate::SLT => signed(lhs) < signed(rhs),
+        IntPredicate::SLE => signed(lhs) <= signed(rhs),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fn_features::FnFeatures, test_utils::module};
+
+    const PREDICATES: [IntPredicate; 10] = [
+        IntPredicate::EQ,
+        IntPredicate::NE,
+        IntPredicate::UGT,
+        IntPredicate::UGE,
+        IntPredicate::ULT,
+        IntPredicate::ULE,
+        IntPredicate::SGT,
+        IntPredicate::SGE,
+        IntPredicate::SLT,
+        IntPredicate::SLE,
+    ];
+
+    const LOOP_IR: &str = r#"
+define void @rotated() {
+entry:
+  br label %loop
+loop:
+  %i = phi i32 [ 0, %entry ], [ %next, %loop ]
+  %next = add i32 %i, 1
+  %c = icmp slt i32 %next, 10
+  br i1 %c, label %loop, label %exit
+exit:
+  ret void
+}
+
+define void @down() {
+entry:
+  br label %header
+header:
+  %i = phi i8 [ 10, %entry ], [ %dec, %body ]
+  %done = icmp eq i8 %i, 0
+  br i1 %done, label %exit, label %body
+body:
+  %dec = sub i8 %i, 1
+  br label %header
+exit:
+  ret void
+}
+
+define void @unbounded(i32 %n) {
+entry:
+  br label %loop
+loop:
+  %i = phi i32 [ 0, %entry ], [ %next, %loop ]
+  %next = add i32 %i, 1
+  %c = icmp slt i32 %next, %n
+  br i1 %c, label %loop, label %exit
+exit:
+  ret void
+}
+"#;
+
+    fn loop_trip_count(function: &str) -> Option<u64> {
+        let module = module(LOOP_IR);
+        let function = module.get_func_by_name(function).unwrap();
+        let features = FnFeatures::from_def(function, &module.types, true);
+        assert_eq!(features.loops.len(), 1);
+        features.loops[0].trip_count
+    }
+
+    #[test]
+    fn finds_constant_trip_counts_of_loops() {
+        // the body runs 10 times, testing the next value
+        assert_eq!(loop_trip_count("rotated"), Some(10));
+        // the header tests 10, 9, ..., 0
+        assert_eq!(loop_trip_count("down"), Some(11));
+        assert_eq!(loop_trip_count("unbounded"), None);
+    }
+
+    #[test]
+    fn counts_relational_exits() {
+        // for (i = 0; i < 10; i++) testing in the header
+        assert_eq!(trip_count(IntPredicate::SGE, 0, 1, 10, 32), Some(11));
+        // signed values start below zero
+        assert_eq!(trip_count(IntPredicate::SGE, 0x80, 1, 0, 8), Some(129));
+        // decrementing through zero to the top of the unsigned range
+        assert_eq!(trip_count(IntPredicate::UGE, 5, u64::MAX, 10, 32), Some(7));
+        // incrementing through the top of the unsigned range to zero
+        assert_eq!(trip_count(IntPredicate::ULT, 250, 1, 5, 8), Some(7));
+        // already out
+        assert_eq!(trip_count(IntPredicate::SGE, 10, 1, 10, 32), Some(1));
+    }
+
+    #[test]
+    fn solves_equality_exits_modulo_the_width() {
+        assert_eq!(trip_count(IntPredicate::EQ, 10, u64::MAX, 0, 32), Some(11));
+        // 3 * 174 == 10 modulo 256
+        assert_eq!(trip_count(IntPredicate::EQ, 0, 3, 10, 8), Some(175));
+        // an even step never reaches an odd distance
+        assert_eq!(trip_count(IntPredicate::EQ, 0, 2, 5, 8), None);
+        assert_eq!(trip_count(IntPredicate::NE, 5, 1, 5, 8), Some(2));
+        assert_eq!(trip_count(IntPredicate::NE, 4, 1, 5, 8), Some(1));
+    }
+
+    #[test]
+    fn gives_up_on_loops_without_a_countable_exit() {
+        assert_eq!(trip_count(IntPredicate::SGE, 0, 0, 10, 32), None);
+        // steps of 100 jump over 251..=255 the first time round
+        assert_eq!(trip_count(IntPredicate::UGT, 0, 100, 250, 8), None);
+        // 2^64 iterations don't fit
+        assert_eq!(trip_count(IntPredicate::EQ, 0, 1, u64::MAX, 64), None);
+    }
+
+    #[test]
+    fn agrees_with_running_the_loop() {
+        const BITS: u32 = 4;
+        for exit in PREDICATES {
+            for first in 0..16 {
+                for step in 0..16 {
+                    for bound in 0..16 {
+                        let run = (0..16u64)
+                            .find(|k| compare(exit, (first + k * step) % 16, bound, BITS))
+                            .map(|k| k + 1);
+                        let closed_form = trip_count(exit, first, step, bound, BITS);
+                        // the closed form may give up, but never disagrees
+                        assert!(
+                            closed_form.is_none() || closed_form == run,
+                            "{:?} {} {} {}: {:?} != {:?}",
+                            exit,
+                            first,
+                            step,
+                            bound,
+                            closed_form,
+                            run
+                        );
+                        if run.is_none() {
+                            assert_eq!(closed_form, None);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::{HashMap, HashSet};
+
+use llvm_ir::{
+    instruction::{Atomicity, MemoryOrdering},
+    BasicBlock, Constant, Function, Instruction, Name, Operand,
+};
+use pyo3::{pyclass, pymethods};
+
+use crate::{attributes::llvm_spelling, call_site::callee_name};
+
+// lowercased llvm-ir memory orderings LLVM spells differently
+const ORDERING_SPELLINGS: [(&str, &str); 2] = [
+    ("acquirerelease", "acq_rel"),
+    ("sequentiallyconsistent", "seq_cst"),
+];
+
+#[pyclass]
+#[derive(Clone, Default)]
+/// Details of the memory operations of a block or function
+pub struct MemoryFeatures {
+    #[pyo3(get)]
+    /// The number of `load` instructions
+    pub load_count: usize,
+    #[pyo3(get)]
+    /// The number of `store` instructions
+    pub store_count: usize,
+    #[pyo3(get)]
+    /// The number of volatile loads
+    pub volatile_load_count: usize,
+    #[pyo3(get)]
+    /// The number of volatile stores
+    pub volatile_store_count: usize,
+    #[pyo3(get)]
+    /// The number of atomic loads
+    pub atomic_load_count: usize,
+    #[pyo3(get)]
+    /// The number of atomic stores
+    pub atomic_store_count: usize,
+    #[pyo3(get)]
+    /// The number of `cmpxchg` instructions
+    pub cmpxchg_count: usize,
+    #[pyo3(get)]
+    /// The number of `atomicrmw` instructions
+    pub atomicrmw_count: usize,
+    #[pyo3(get)]
+    /// The number of `fence` instructions
+    pub fence_count: usize,
+    #[pyo3(get)]
+    /// How many atomic operations use each memory ordering (`monotonic`, `acquire`,
+    /// `seq_cst`, ...), a `cmpxchg` counted by its success ordering
+    pub orderings: HashMap<String, usize>,
+    #[pyo3(get)]
+    /// How many loads and stores have each alignment, in bytes
+    pub alignments: HashMap<u32, usize>,
+    #[pyo3(get)]
+    /// The number of loads from an address computed by a `getelementptr`
+    pub gep_load_count: usize,
+    #[pyo3(get)]
+    /// The number of stores to an address computed by a `getelementptr`
+    pub gep_store_count: usize,
+    #[pyo3(get)]
+    /// The number of `getelementptr` instructions
+    pub gep_count: usize,
+    #[pyo3(get)]
+    /// The number of indices over every `getelementptr`
+    pub gep_index_count: usize,
+    #[pyo3(get)]
+    /// The number of constant indices over every `getelementptr`
+    pub gep_const_index_count: usize,
+    #[pyo3(get)]
+    /// The number of calls to the `llvm.memcpy` intrinsics, `llvm.memcpy.inline` included
+    pub memcpy_count: usize,
+    #[pyo3(get)]
+    /// The number of calls to the `llvm.memset` intrinsics, `llvm.memset.inline` included
+    pub memset_count: usize,
+    #[pyo3(get)]
+    /// The number of calls to the `llvm.memmove` intrinsics
+    pub memmove_count: usize,
+}
+
+impl MemoryFeatures {
+    /// `gep_results` are the values of the function computed by a `getelementptr`, see
+    /// `gep_results()`
+    pub fn new(bb: &BasicBlock, gep_results: &HashSet<&Name>) -> Self {
+        let mut features = Self::default();
+        let is_gep = |address: &Operand| match address {
+            Operand::LocalOperand { name, .. } => gep_results.contains(name),
+            Operand::ConstantOperand(constant) => {
+                matches!(constant.as_ref(), Constant::GetElementPtr(_))
+            }
+            Operand::MetadataOperand => false,
+        };
+        for instr in bb.instrs.iter() {
+            match instr {
+                Instruction::Load(load) => {
+                    features.load_count += 1;
+                    features.volatile_load_count += load.volatile as usize;
+                    if let Some(atomicity) = &load.atomicity {
+                        features.atomic_load_count += 1;
+                        features.count_ordering(atomicity);
+                    }
+                    *features.alignments.entry(load.alignment).or_insert(0) += 1;
+                    features.gep_load_count += is_gep(&load.address) as usize;
+                }
+                Instruction::Store(store) => {
+                    features.store_count += 1;
+                    features.volatile_store_count += store.volatile as usize;
+                    if let Some(atomicity) = &store.atomicity {
+                        features.atomic_store_count += 1;
+                        features.count_ordering(atomicity);
+                    }
+                    *features.alignments.entry(store.alignment).or_insert(0) += 1;
+                    features.gep_store_count += is_gep(&store.address) as usize;
+                }
+                Instruction::CmpXchg(cmpxchg) => {
+                    features.cmpxchg_count += 1;
+                    features.count_ordering(&cmpxchg.atomicity);
+                }
+                Instruction::AtomicRMW(atomicrmw) => {
+                    features.atomicrmw_count += 1;
+                    features.count_ordering(&atomicrmw.atomicity);
+                }
+                Instruction::Fence(fence) => {
+                    features.fence_count += 1;
+                    features.count_ordering(&fence.atomicity);
+                }
+                Instruction::GetElementPtr(gep) => {
+                    features.gep_count += 1;
+                    features.gep_index_count += gep.indices.len();
+                    features.gep_const_index_count += gep
+                        .indices
+                        .iter()
+                        .filter(|index| matches!(index, Operand::ConstantOperand(_)))
+                        .count();
+                }
+                Instruction::Call(call) => {
+                    let callee = callee_name(call.function.as_ref().right());
+                    match callee
+                        .as_deref()
+                        .and_then(|callee| callee.strip_prefix("llvm."))
+                    {
+                        Some(intrinsic) if intrinsic.starts_with("memcpy") => {
+                            features.memcpy_count += 1
+                        }
+                        Some(intrinsic) if intrinsic.starts_with("memset") => {
+                            features.memset_count += 1
+                        }
+                        Some(intrinsic) if intrinsic.starts_with("memmove") => {
+                            features.memmove_count += 1
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        features
+    }
+
+    /// Adds the counts of another block
+    pub fn add(&mut self, other: &MemoryFeatures) {
+        self.load_count += other.load_count;
+        self.store_count += other.store_count;
+        self.volatile_load_count += other.volatile_load_count;
+        self.volatile_store_count += other.volatile_store_count;
+        self.atomic_load_count += other.atomic_load_count;
+        self.atomic_store_count += other.atomic_store_count;
+        self.cmpxchg_count += other.cmpxchg_count;
+        self.atomicrmw_count += other.atomicrmw_count;
+        self.fence_count += other.fence_count;
+        for (ordering, count) in other.orderings.iter() {
+            *self.orderings.entry(ordering.clone()).or_insert(0) += count;
+        }
+        for (alignment, count) in other.alignments.iter() {
+            *self.alignments.entry(*alignment).or_insert(0) += count;
+        }
+        self.gep_load_count += other.gep_load_count;
+        self.gep_store_count += other.gep_store_count;
+        self.gep_count += other.gep_count;
+        self.gep_index_count += other.gep_index_count;
+        self.gep_const_index_count += other.gep_const_index_count;
+        self.memcpy_count += other.memcpy_count;
+        self.memset_count += other.memset_count;
+        self.memmove_count += other.memmove_count;
+    }
+
+    fn count_ordering(&mut self, atomicity: &Atomicity) {
+        if atomicity.mem_ordering != MemoryOrdering::NotAtomic {
+            let ordering = llvm_spelling(&atomicity.mem_ordering, &ORDERING_SPELLINGS);
+            *self.orderings.entry(ordering).or_insert(0) += 1;
+        }
+    }
+}
+
+#[pymethods]
+impl MemoryFeatures {
+    /// The share of `getelementptr` indices that are constants, 0 without indices
+    pub fn gep_const_index_ratio(&self) -> f64 {
+        self.gep_const_index_count as f64 / self.gep_index_count.max(1) as f64
+    }
+
+    /// The share of loads and stores going through a `getelementptr` address rather
+    /// than a plain pointer, 0 without loads or stores
+    pub fn gep_access_ratio(&self) -> f64 {
+        let gep_access_count = self.gep_load_count + self.gep_store_count;
+        gep_access_count as f64 / (self.load_count + self.store_count).max(1) as f64
+    }
+}
+
+/// The values of a function computed by a `getelementptr` instruction
+pub fn gep_results(function: &Function) -> HashSet<&Name> {
+    function
+        .basic_blocks
+        .iter()
+        .flat_map(|bb| bb.instrs.iter())
+        .filter_map(|instr| match instr {
+            Instruction::GetElementPtr(gep) => Some(&gep.dest),
+            _ => None,
+        })
+        .collect()
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use polars::prelude::*;
+use pyo3::{prelude::*, PyResult};
+
+use crate::{
+    inline_features::InlineOutcome,
+    llvm_sys_wrapper::{inline_cost_feature_names, InlineCost},
+    mod_features::ModFeatures,
+    remarks::InlineRemark,
+};
+
+/*
+The features of LLVM's ML inline advisor, named and ordered as in
+InlineModelFeatureMaps.h, all Int64:
+- sroa_savings, ..., threshold: the InlineCost features of the linked LLVM, from
+  llvm::getInliningCostFeatures
+- callee_basic_block_count: reachable blocks of the callee
+- callsite_height: the caller's height in the call graph
+- node_count: defined functions in the module
+- nr_ctant_params: constant arguments of the call
+- cost_estimate: llvm::getInliningCostEstimate
+- edge_count: direct calls to defined functions in the module
+- caller_users / callee_users: uses of the function, +1 unless it has local linkage
+- caller_conditionally_executed_blocks / callee_conditionally_executed_blocks
+- caller_basic_block_count: reachable blocks of the caller
+- is_callee_avail_external / is_caller_avail_external
+- inlining_default: 1 if the default inliner inlined the call, null when unknown
+
+call_site_id, caller_name and callee_name lead each row to identify it. The values
+are those the ML advisor sees before the first inlining decision in the module. The
+InlineCost features and cost_estimate are null for calls the cost analysis finds can't
+be inlined, which the ML advisor never evaluates.
+*/
+
+/// Builds the MLGO features for every call to a function defined in the module, the
+/// cost features coming from `inline_costs` by call site id. `min_inline_thresholds`
+/// adds the threshold sweep's column when one was run.
+pub fn mlgo_features(
+    mod_features: &ModFeatures,
+    mut labels: HashMap<String, (InlineOutcome, Option<InlineRemark>)>,
+    inline_costs: &HashMap<String, InlineCost>,
+    min_inline_thresholds: Option<&HashMap<String, i32>>,
+) -> PyResult<DataFrame> {
+    let cost_feature_names = inline_cost_feature_names();
+    let call_graph = &mod_features.call_graph;
+    let heights = call_graph.heights();
+    let node_count = call_graph.functions.len() as i64;
+    let is_defined = |name: &str| {
+        mod_features
+            .fn_feats
+            .get(name)
+            .is_some_and(|features| !features.is_declaration)
+    };
+    let edge_count = mod_features
+        .call_sites
+        .iter()
+        .filter(|site| site.callee.as_deref().is_some_and(is_defined))
+        .count() as i64;
+
+    let mut call_site_id_vec: Vec<String> = vec![];
+    let mut caller_name_vec: Vec<String> = vec![];
+    let mut callee_name_vec: Vec<String> = vec![];
+    let mut cost_feature_vecs: Vec<Vec<Option<i64>>> = vec![vec![]; cost_feature_names.len()];
+    let mut callee_basic_block_count_vec: Vec<i64> = vec![];
+    let mut callsite_height_vec: Vec<i64> = vec![];
+    let mut nr_ctant_params_vec: Vec<i64> = vec![];
+    let mut cost_estimate_vec: Vec<Option<i64>> = vec![];
+    let mut caller_users_vec: Vec<i64> = vec![];
+    let mut caller_conditionally_executed_blocks_vec: Vec<i64> = vec![];
+    let mut caller_basic_block_count_vec: Vec<i64> = vec![];
+    let mut callee_conditionally_executed_blocks_vec: Vec<i64> = vec![];
+    let mut callee_users_vec: Vec<i64> = vec![];
+    let mut is_callee_avail_external_vec: Vec<i64> = vec![];
+    let mut is_caller_avail_external_vec: Vec<i64> = vec![];
+    let mut inlining_default_vec: Vec<Option<i64>> = vec![];
+    let mut min_inline_threshold_vec: Vec<Option<i32>> = vec![];
+
+    for call_site in mod_features.call_sites.iter() {
+        // the ML advisor is only consulted for calls to defined functions
+        let Some(callee_name) = call_site.callee.as_ref().filter(|name| is_defined(name)) else {
+            continue;
+        };
+        let callee = &mod_features.fn_feats[callee_name];
+        let caller = &mod_features.fn_feats[&call_site.caller];
+        let caller_height = call_graph
+            .position(&call_site.caller)
+            .map_or(0, |position| heights[position]);
+        let call_site_id = call_site.id();
+        let outcome = labels
+            .remove(&call_site_id)
+            .map_or(InlineOutcome::Unknown, |(outcome, _)| outcome);
+
+        if let Some(min_inline_thresholds) = min_inline_thresholds {
+            min_inline_threshold_vec.push(min_inline_thresholds.get(&call_site_id).copied());
+        }
+        call_site_id_vec.push(call_site_id);
+        caller_name_vec.push(call_site.caller.clone());
+        callee_name_vec.push(callee_name.clone());
+        let cost = inline_costs.get(&call_site_id);
+        for (index, feature_vec) in cost_feature_vecs.iter_mut().enumerate() {
+            feature_vec.push(cost.map(|cost| cost.features[index]));
+        }
+        callee_basic_block_count_vec.push(callee.reachable_bb_count as i64);
+        callsite_height_vec.push(caller_height as i64);
+        nr_ctant_params_vec.push(call_site.const_arg_count as i64);
+        cost_estimate_vec.push(cost.map(|cost| cost.estimate));
+        caller_users_vec.push(caller.users as i64);
+        caller_conditionally_executed_blocks_vec
+            .push(caller.conditionally_executed_block_count as i64);
+        caller_basic_block_count_vec.push(caller.reachable_bb_count as i64);
+        callee_conditionally_executed_blocks_vec
+            .push(callee.conditionally_executed_block_count as i64);
+        callee_users_vec.push(callee.users as i64);
+        is_callee_avail_external_vec.push(callee.is_available_externally as i64);
+        is_caller_avail_external_vec.push(caller.is_available_externally as i64);
+        inlining_default_vec.push(outcome.was_inlined().map(i64::from));
+    }
+
+    let row_count = call_site_id_vec.len();
+    let mut columns = vec![
+        Column::new("call_site_id".into(), call_site_id_vec),
+        Column::new("caller_name".into(), caller_name_vec),
+        Column::new("callee_name".into(), callee_name_vec),
+    ];
+    // the InlineCost features come first
+    columns.extend(
+        cost_feature_names
+            .iter()
+            .zip(cost_feature_vecs)
+            .map(|(name, feature_vec)| Column::new(name.as_str().into(), feature_vec)),
+    );
+    columns.extend([
+        Column::new(
+            "callee_basic_block_count".into(),
+            callee_basic_block_count_vec,
+        ),
+        Column::new("callsite_height".into(), callsite_height_vec),
+        Column::new("node_count".into(), vec![node_count; row_count]),
+        Column::new("nr_ctant_params".into(), nr_ctant_params_vec),
+        Column::new("cost_estimate".into(), cost_estimate_vec),
+        Column::new("edge_count".into(), vec![edge_count; row_count]),
+        Column::new("caller_users".into(), caller_users_vec),
+        Column::new(
+            "caller_conditionally_executed_blocks".into(),
+            caller_conditionally_executed_blocks_vec,
+        ),
+        Column::new(
+            "caller_basic_block_count".into(),
+            caller_basic_block_count_vec,
+        ),
+        Column::new(
+            "callee_conditionally_executed_blocks".into(),
+            callee_conditionally_executed_blocks_vec,
+        ),
+        Column::new("callee_users".into(), callee_users_vec),
+        Column::new(
+            "is_callee_avail_external".into(),
+            is_callee_avail_external_vec,
+        ),
+        Column::new(
+            "is_caller_avail_external".into(),
+            is_caller_avail_external_vec,
+        ),
+        Column::new("inlining_default".into(), inlining_default_vec),
+    ]);
+    if min_inline_thresholds.is_some() {
+        columns.push(Column::new(
+            "min_inline_threshold".into(),
+            min_inline_threshold_vec,
+        ));
+    }
+
+    DataFrame::new(columns)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{llvm_sys_wrapper::inline_costs, test_utils::bitcode};
+
+    const IR: &str = r#"
+@fp = global ptr @leaf
+
+define internal i32 @leaf(i32 %x) {
+entry:
+  %c = icmp eq i32 %x, 0
+  br i1 %c, label %a, label %b
+a:
+  ret i32 1
+b:
+  ret i32 2
+}
+
+define i32 @mid(i32 %x) {
+entry:
+  %y = call i32 @leaf(i32 %x)
+  ret i32 %y
+}
+
+define i32 @top(i32 %x) {
+entry:
+  %a = call i32 @mid(i32 5)
+  %b = call i32 @leaf(i32 %a)
+  %c = call i32 @ext()
+  ret i32 %c
+}
+
+declare i32 @ext()
+"#;
+
+    fn features() -> DataFrame {
+        let bc = bitcode(IR);
+        let mod_features = ModFeatures::from_bc(&bc, true).unwrap();
+        let labels = HashMap::from([("top:0:0".to_string(), (InlineOutcome::Inlined, None))]);
+        mlgo_features(&mod_features, labels, &inline_costs(&bc).unwrap(), None).unwrap()
+    }
+
+    fn int_column(df: &DataFrame, name: &str) -> Vec<Option<i64>> {
+        df.column(name).unwrap().i64().unwrap().iter().collect()
+    }
+
+    #[test]
+    fn orders_features_as_mlgo() {
+        let df = features();
+        let names: Vec<&str> = df
+            .get_column_names()
+            .iter()
+            .map(|name| name.as_str())
+            .collect();
+        let cost_feature_names = inline_cost_feature_names();
+        let cost_feature_count = cost_feature_names.len();
+        assert_eq!(names[..3], ["call_site_id", "caller_name", "callee_name"]);
+        assert_eq!(names[3..3 + cost_feature_count], cost_feature_names);
+        assert_eq!(cost_feature_names[0], "sroa_savings");
+        assert_eq!(cost_feature_names[cost_feature_count - 1], "threshold");
+        assert_eq!(
+            names[3 + cost_feature_count..3 + cost_feature_count + 6],
+            [
+                "callee_basic_block_count",
+                "callsite_height",
+                "node_count",
+                "nr_ctant_params",
+                "cost_estimate",
+                "edge_count"
+            ]
+        );
+        // every call can be inlined, so only the label can be missing
+        for column in df
+            .get_columns()
+            .iter()
+            .filter(|column| column.name().as_str() != "inlining_default")
+        {
+            assert_eq!(column.null_count(), 0, "{} has nulls", column.name());
+        }
+    }
+
+    #[test]
+    fn computes_module_and_call_site_features() {
+        let df = features();
+        // the call to the declared @ext is not a row
+        let ids: Vec<Option<&str>> = df
+            .column("call_site_id")
+            .unwrap()
+            .str()
+            .unwrap()
+            .iter()
+            .collect();
+        assert_eq!(ids, [Some("mid:0:0"), Some("top:0:0"), Some("top:0:1")]);
+        assert_eq!(int_column(&df, "node_count"), [Some(3); 3]);
+        assert_eq!(int_column(&df, "edge_count"), [Some(3); 3]);
+        assert_eq!(
+            int_column(&df, "callsite_height"),
+            [Some(1), Some(2), Some(2)]
+        );
+        assert_eq!(
+            int_column(&df, "nr_ctant_params"),
+            [Some(0), Some(1), Some(0)]
+        );
+        assert_eq!(
+            int_column(&df, "callee_basic_block_count"),
+            [Some(3), Some(1), Some(3)]
+        );
+        assert_eq!(
+            int_column(&df, "callee_conditionally_executed_blocks"),
+            [Some(2), Some(0), Some(2)]
+        );
+        // @leaf: two calls and @fp, local; @mid: one call and the world outside
+        assert_eq!(int_column(&df, "callee_users"), [Some(3), Some(2), Some(3)]);
+    }
+
+    #[test]
+    fn takes_the_cost_features_from_llvm() {
+        let df = features();
+        // only @mid is called with a constant
+        assert_eq!(
+            int_column(&df, "constant_args"),
+            [Some(0), Some(1), Some(0)]
+        );
+    }
+
+    #[test]
+    fn unlabelled_calls_have_no_default_decision() {
+        let df = features();
+        assert_eq!(int_column(&df, "inlining_default"), [None, Some(1), None]);
+    }
+}
@@ -1,29 +1,47 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
 
-use llvm_ir::Module;
+use llvm_ir::{function::ParameterAttribute, Constant, Instruction, Module, Operand, Terminator};
 use pyo3::{prelude::*, pyclass, pymethods, types::PyBytes, Bound, PyResult};
 use rayon::prelude::*;
 
-use crate::{fn_features::FnFeatures, llvm_sys_wrapper::run_inline_pass};
+use crate::{
+    autophase::AUTOPHASE_FEATURE_NAMES,
+    call_graph::CallGraph,
+    call_site::{callee_name, global_name, CallSite},
+    fn_features::FnFeatures,
+    inst_count::{normalize, INST_COUNT_FEATURE_NAMES},
+    ir2vec::SymbolicFunction,
+    llvm_sys_wrapper::memory_effects,
+    operands::{instruction_operands, terminator_operands},
+};
 
 #[pyclass]
 pub struct ModFeatures {
     #[pyo3(get)]
     pub fn_feats: HashMap<String, FnFeatures>,
     #[pyo3(get)]
-    /// (caller_name, bb_name, callee_name)
-    pub call_sites: HashSet<(String, String, String)>,
+    /// Every call-like site in the module, in function, block and instruction order
+    pub call_sites: Vec<CallSite>,
+    #[pyo3(get)]
+    /// The direct call graph between the defined functions
+    pub call_graph: CallGraph,
+    // the bitcode, converted again for IR2Vec on first use
+    bc: Vec<u8>,
+    symbolic_functions: OnceLock<HashMap<String, SymbolicFunction>>,
 }
 
 impl ModFeatures {
-    fn from_bc(bc: &[u8]) -> Result<Self, String> {
+    pub fn from_bc(bc: &[u8], include_terminators: bool) -> Result<Self, String> {
         let module = Module::from_bc_bytes(bc)?;
         // get fn definitions
         let mut fn_feats: HashMap<String, FnFeatures> = module
             .functions
             .par_iter()
             .map(|func| {
-                let stats = FnFeatures::from_def(&func);
+                let stats = FnFeatures::from_def(&func, &module.types, include_terminators);
                 (stats.name.clone(), stats)
             })
             .collect();
@@ -32,32 +50,414 @@ impl ModFeatures {
             let stats = FnFeatures::from_declaration(&decl);
             (stats.name.clone(), stats)
         }));
-        let call_sites = fn_feats
-            .values()
-            .flat_map(|func| {
-                func.calls
-                    .iter()
-                    .map(|(bb_name, callee_name)| (func.name.clone(), bb_name.clone(), callee_name.clone()))
-            })
+
+        let call_sites: Vec<CallSite> = module
+            .functions
+            .iter()
+            .flat_map(|func| fn_feats[&func.name.to_string()].calls.iter().cloned())
             .collect();
+
+        // direct calls, and the uses of a function that are not one
+        let mut callers: HashSet<(&str, &str)> = HashSet::new();
+        for call_site in call_sites.iter() {
+            let Some(callee) = &call_site.callee else {
+                continue;
+            };
+            if let Some(features) = fn_feats.get_mut(callee) {
+                features.static_call_count += 1;
+                if callers.insert((call_site.caller.as_str(), callee.as_str())) {
+                    features.static_caller_count += 1;
+                }
+            }
+        }
+        let non_call_uses = non_call_uses(&module);
+        for features in fn_feats.values_mut() {
+            features.address_taken_count = non_call_uses.get(&features.name).copied().unwrap_or(0);
+            // plus one for the world outside the module unless the linkage is local
+            features.users = features.static_call_count
+                + features.address_taken_count
+                + u64::from(!features.has_local_linkage);
+            features.is_last_call_to_static = features.has_local_linkage
+                && features.static_call_count == 1
+                && features.address_taken_count == 0;
+        }
+        let call_graph = CallGraph::new(
+            module
+                .functions
+                .iter()
+                .map(|func| &fn_feats[&func.name.to_string()]),
+        );
+        for (scc_id, scc) in call_graph.sccs.iter().enumerate() {
+            for &function in scc {
+                let features = fn_feats.get_mut(&call_graph.functions[function]).unwrap();
+                features.scc_id = Some(scc_id as u64);
+                features.scc_size = scc.len() as u64;
+                features.is_recursive |= scc.len() > 1;
+            }
+        }
         Ok(Self {
             fn_feats,
             call_sites,
+            call_graph,
+            bc: bc.to_vec(),
+            symbolic_functions: OnceLock::new(),
         })
     }
 
-    pub fn inlined_mod_features(bc: &[u8]) -> Result<Self, String> {
-        let inlined_bc = run_inline_pass(bc)?;
-        Self::from_bc(&inlined_bc)
+    /// Every defined function as IR2Vec embeds it, built on first use so that the other
+    /// features don't pay for it
+    pub fn symbolic_functions(&self) -> Result<&HashMap<String, SymbolicFunction>, String> {
+        if let Some(functions) = self.symbolic_functions.get() {
+            return Ok(functions);
+        }
+        let module = Module::from_bc_bytes(&self.bc)?;
+        let functions = module
+            .functions
+            .par_iter()
+            .map(|func| {
+                (
+                    func.name.to_string(),
+                    SymbolicFunction::new(func, &module.types),
+                )
+            })
+            .collect();
+        Ok(self.symbolic_functions.get_or_init(|| functions))
+    }
+
+    /// Reads the functions' memory effects, which llvm-ir does not decode, from LLVM's
+    /// own parse of `bc`. If LLVM rejects the bitcode they stay `MemoryEffects::UNKNOWN`.
+    pub fn read_memory_effects(&mut self, bc: &[u8]) {
+        let Ok(effects) = memory_effects(bc) else {
+            return;
+        };
+        for (name, effects) in effects {
+            if let Some(features) = self.fn_feats.get_mut(&name) {
+                features.memory_effects = effects;
+            }
+        }
     }
 }
 
 #[pymethods]
 impl ModFeatures {
     #[new]
-    pub fn new(bc: Bound<PyBytes>) -> PyResult<Self> {
+    #[pyo3(signature = (bc, include_terminators = true, memory_effects = true))]
+    pub fn new(
+        bc: Bound<PyBytes>,
+        include_terminators: bool,
+        memory_effects: bool,
+    ) -> PyResult<Self> {
         let bc = bc.as_bytes();
-        Self::from_bc(bc)
-            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+        let mut mod_features = Self::from_bc(bc, include_terminators)
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+        // the memory effects take a second parse of the bitcode
+        if memory_effects {
+            mod_features.read_memory_effects(bc);
+        }
+        Ok(mod_features)
+    }
+
+    /// The 56 Autophase features of the module, the sum of its functions' vectors
+    pub fn autophase(&self) -> Vec<u64> {
+        let mut features = vec![0; AUTOPHASE_FEATURE_NAMES.len()];
+        for fn_feats in self.fn_feats.values() {
+            for (total, count) in features.iter_mut().zip(fn_feats.autophase()) {
+                *total += count;
+            }
+        }
+        features
+    }
+
+    /// CompilerGym's 70-dimensional InstCount vector of the module, functions counting
+    /// its definitions
+    pub fn inst_count(&self) -> Vec<u64> {
+        let mut features = vec![0; INST_COUNT_FEATURE_NAMES.len()];
+        for fn_feats in self.fn_feats.values() {
+            for (total, count) in features.iter_mut().zip(fn_feats.inst_count()) {
+                *total += count;
+            }
+        }
+        features
+    }
+
+    /// The 69 InstCount densities of the module, as CompilerGym's InstCountNorm
+    pub fn inst_count_norm(&self) -> Vec<f64> {
+        normalize(&self.inst_count())
+    }
+}
+
+// the number of uses of each global other than as the callee of a direct call, from
+// instructions, global initializers and aliases, counting uses nested in constant
+// aggregates and expressions
+fn non_call_uses(module: &Module) -> HashMap<String, u64> {
+    let mut uses = HashMap::new();
+    for func in module.functions.iter() {
+        for bb in func.basic_blocks.iter() {
+            let operands = bb
+                .instrs
+                .iter()
+                .flat_map(instruction_uses)
+                .chain(terminator_uses(&bb.term));
+            for operand in operands {
+                if let Operand::ConstantOperand(constant) = operand {
+                    count_uses(constant, &mut uses);
+                }
+            }
+        }
+    }
+    for global in module.global_vars.iter() {
+        if let Some(initializer) = &global.initializer {
+            count_uses(initializer, &mut uses);
+        }
+    }
+    for alias in module.global_aliases.iter() {
+        count_uses(&alias.aliasee, &mut uses);
+    }
+    uses
+}
+
+// the operands of an instruction but the callee of a direct call
+fn instruction_uses(instruction: &Instruction) -> Vec<&Operand> {
+    match instruction {
+        Instruction::Call(call) => call_uses(&call.arguments, call.function.as_ref().right()),
+        _ => instruction_operands(instruction),
+    }
+}
+
+fn terminator_uses(terminator: &Terminator) -> Vec<&Operand> {
+    match terminator {
+        Terminator::Invoke(invoke) => {
+            call_uses(&invoke.arguments, invoke.function.as_ref().right())
+        }
+        Terminator::CallBr(callbr) => {
+            call_uses(&callbr.arguments, callbr.function.as_ref().right())
+        }
+        _ => terminator_operands(terminator),
+    }
+}
+
+fn call_uses<'a>(
+    arguments: &'a [(Operand, Vec<ParameterAttribute>)],
+    function: Option<&'a Operand>,
+) -> Vec<&'a Operand> {
+    arguments
+        .iter()
+        .map(|(argument, _)| argument)
+        .chain(function.filter(|function| callee_name(Some(*function)).is_none()))
+        .collect()
+}
+
+fn count_uses(constant: &Constant, uses: &mut HashMap<String, u64>) {
+    match constant {
+        Constant::GlobalReference { name, .. } => {
+            *uses.entry(global_name(name)).or_insert(0) += 1;
+        }
+        Constant::Struct { values, .. } => values.iter().for_each(|value| count_uses(value, uses)),
+        Constant::Array { elements, .. } | Constant::Vector(elements) => elements
+            .iter()
+            .for_each(|element| count_uses(element, uses)),
+        Constant::GetElementPtr(gep) => std::iter::once(&gep.address)
+            .chain(gep.indices.iter())
+            .for_each(|operand| count_uses(operand, uses)),
+        Constant::BitCast(cast) => count_uses(&cast.operand, uses),
+        Constant::PtrToInt(cast) => count_uses(&cast.operand, uses),
+        Constant::IntToPtr(cast) => count_uses(&cast.operand, uses),
+        Constant::AddrSpaceCast(cast) => count_uses(&cast.operand, uses),
+        Constant::Add(add) => {
+            count_uses(&add.operand0, uses);
+            count_uses(&add.operand1, uses);
+        }
+        Constant::Sub(sub) => {
+            count_uses(&sub.operand0, uses);
+            count_uses(&sub.operand1, uses);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::bitcode;
+
+    const RECURSIVE_IR: &str = r#"
+define void @even() {
+entry:
+  call void @odd()
+  ret void
+}
+
+define void @odd() {
+entry:
+  call void @even()
+  ret void
+}
+
+define void @rec() {
+entry:
+  call void @rec()
+  ret void
+}
+
+define void @main() {
+entry:
+  call void @even()
+  call void @rec()
+  call void @ext()
+  ret void
+}
+
+declare void @ext()
+"#;
+
+    #[test]
+    fn detects_mutual_recursion_through_sccs() {
+        let mod_features = ModFeatures::from_bc(&bitcode(RECURSIVE_IR), true).unwrap();
+        let features = |name: &str| &mod_features.fn_feats[name];
+        for name in ["even", "odd"] {
+            assert!(features(name).is_recursive);
+            assert!(!features(name).is_directly_recursive);
+            assert_eq!(features(name).scc_id, Some(0));
+            assert_eq!(features(name).scc_size, 2);
+        }
+        assert!(features("rec").is_recursive);
+        assert!(features("rec").is_directly_recursive);
+        assert_eq!(features("rec").scc_id, Some(1));
+        assert_eq!(features("rec").scc_size, 1);
+        assert!(!features("main").is_recursive);
+        assert_eq!(features("main").scc_id, Some(2));
+        assert_eq!(features("main").scc_size, 1);
+    }
+
+    #[test]
+    fn declarations_are_in_no_scc() {
+        let mod_features = ModFeatures::from_bc(&bitcode(RECURSIVE_IR), true).unwrap();
+        let ext = &mod_features.fn_feats["ext"];
+        assert!(!ext.is_recursive);
+        assert_eq!(ext.scc_id, None);
+        assert_eq!(ext.scc_size, 0);
+    }
+
+    #[test]
+    fn builds_symbolic_functions_on_first_use() {
+        let mod_features = ModFeatures::from_bc(&bitcode(RECURSIVE_IR), true).unwrap();
+        assert!(mod_features.symbolic_functions.get().is_none());
+        assert_eq!(mod_features.symbolic_functions().unwrap().len(), 4);
+        assert!(mod_features.symbolic_functions.get().is_some());
+    }
+
+    #[test]
+    fn decodes_memory_attributes() {
+        let ir = r#"
+define i32 @get(ptr %p) memory(argmem: read) {
+entry:
+  %v = load i32, ptr %p
+  ret i32 %v
+}
+
+define void @plain() {
+entry:
+  ret void
+}
+
+declare i32 @pure(i32) memory(none)
+"#;
+        let bc = bitcode(ir);
+        let mut mod_features = ModFeatures::from_bc(&bc, true).unwrap();
+        // only read on request
+        let get = &mod_features.fn_feats["get"];
+        assert_eq!(get.memory_effects_string(), "memory(readwrite)");
+
+        mod_features.read_memory_effects(&bc);
+        let get = &mod_features.fn_feats["get"];
+        assert_eq!(get.memory_effects_string(), "memory(argmem: read)");
+        assert!(get.has_attribute("readonly"));
+        assert!(get.has_attribute("argmemonly"));
+        assert!(!get.has_attribute("readnone"));
+        assert!(!get.has_attribute("writeonly"));
+
+        let pure = &mod_features.fn_feats["pure"];
+        assert_eq!(pure.memory_effects_string(), "memory(none)");
+        assert!(pure.has_attribute("readnone"));
+
+        let plain = &mod_features.fn_feats["plain"];
+        assert_eq!(plain.memory_effects_string(), "memory(readwrite)");
+        assert!(!plain.has_attribute("readonly"));
+    }
+
+    #[test]
+    fn keeps_unknown_memory_effects_when_llvm_rejects_the_bitcode() {
+        let mut mod_features = ModFeatures::from_bc(&bitcode(RECURSIVE_IR), true).unwrap();
+        mod_features.read_memory_effects(b"not bitcode");
+        assert_eq!(
+            mod_features.fn_feats["main"].memory_effects_string(),
+            "memory(readwrite)"
+        );
+    }
+
+    #[test]
+    fn counts_uses_inside_constant_expressions() {
+        let ir = r#"
+@int = global i64 ptrtoint (ptr @a to i64)
+@offset = global ptr getelementptr (i8, ptr @b, i64 1)
+@alias = alias void (), ptr @c
+@slot = global ptr null
+
+define internal void @a() {
+entry:
+  ret void
+}
+
+define internal void @b() {
+entry:
+  ret void
+}
+
+define internal void @c() {
+entry:
+  ret void
+}
+
+define internal void @d() {
+entry:
+  ret void
+}
+
+define internal void @e() {
+entry:
+  ret void
+}
+
+define void @user() {
+entry:
+  call void @a()
+  call void @d()
+  call void @e()
+  store ptr @d, ptr @slot
+  ret void
+}
+"#;
+        let mod_features = ModFeatures::from_bc(&bitcode(ir), true).unwrap();
+        let uses = |name: &str| {
+            let features = &mod_features.fn_feats[name];
+            (
+                features.static_call_count,
+                features.address_taken_count,
+                features.users,
+            )
+        };
+        // called, and taken through ptrtoint
+        assert_eq!(uses("a"), (1, 1, 2));
+        // taken through a getelementptr
+        assert_eq!(uses("b"), (0, 1, 1));
+        // aliased
+        assert_eq!(uses("c"), (0, 1, 1));
+        // called, and stored
+        assert_eq!(uses("d"), (1, 1, 2));
+        assert_eq!(uses("e"), (1, 0, 1));
+        // only used from outside the module
+        assert_eq!(uses("user"), (0, 0, 1));
+        assert!(mod_features.fn_feats["e"].is_last_call_to_static);
+        assert!(!mod_features.fn_feats["a"].is_last_call_to_static);
     }
 }
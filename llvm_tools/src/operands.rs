@@ -0,0 +1,100 @@
+use llvm_ir::{Instruction, Operand, Terminator};
+
+/// Every operand of an instruction in operand order, the called function included
+pub fn instruction_operands(instruction: &Instruction) -> Vec<&Operand> {
+    match instruction {
+        Instruction::Add(i) => vec![&i.operand0, &i.operand1],
+        Instruction::Sub(i) => vec![&i.operand0, &i.operand1],
+        Instruction::Mul(i) => vec![&i.operand0, &i.operand1],
+        Instruction::UDiv(i) => vec![&i.operand0, &i.operand1],
+        Instruction::SDiv(i) => vec![&i.operand0, &i.operand1],
+        Instruction::URem(i) => vec![&i.operand0, &i.operand1],
+        Instruction::SRem(i) => vec![&i.operand0, &i.operand1],
+        Instruction::And(i) => vec![&i.operand0, &i.operand1],
+        Instruction::Or(i) => vec![&i.operand0, &i.operand1],
+        Instruction::Xor(i) => vec![&i.operand0, &i.operand1],
+        Instruction::Shl(i) => vec![&i.operand0, &i.operand1],
+        Instruction::LShr(i) => vec![&i.operand0, &i.operand1],
+        Instruction::AShr(i) => vec![&i.operand0, &i.operand1],
+        Instruction::FAdd(i) => vec![&i.operand0, &i.operand1],
+        Instruction::FSub(i) => vec![&i.operand0, &i.operand1],
+        Instruction::FMul(i) => vec![&i.operand0, &i.operand1],
+        Instruction::FDiv(i) => vec![&i.operand0, &i.operand1],
+        Instruction::FRem(i) => vec![&i.operand0, &i.operand1],
+        Instruction::FNeg(i) => vec![&i.operand],
+        Instruction::ExtractElement(i) => vec![&i.vector, &i.index],
+        Instruction::InsertElement(i) => vec![&i.vector, &i.element, &i.index],
+        Instruction::ShuffleVector(i) => vec![&i.operand0, &i.operand1],
+        Instruction::ExtractValue(i) => vec![&i.aggregate],
+        Instruction::InsertValue(i) => vec![&i.aggregate, &i.element],
+        Instruction::Alloca(i) => vec![&i.num_elements],
+        Instruction::Load(i) => vec![&i.address],
+        Instruction::Store(i) => vec![&i.value, &i.address],
+        Instruction::Fence(_) => vec![],
+        Instruction::CmpXchg(i) => vec![&i.address, &i.expected, &i.replacement],
+        Instruction::AtomicRMW(i) => vec![&i.address, &i.value],
+        Instruction::GetElementPtr(i) => std::iter::once(&i.address)
+            .chain(i.indices.iter())
+            .collect(),
+        Instruction::Trunc(i) => vec![&i.operand],
+        Instruction::ZExt(i) => vec![&i.operand],
+        Instruction::SExt(i) => vec![&i.operand],
+        Instruction::FPTrunc(i) => vec![&i.operand],
+        Instruction::FPExt(i) => vec![&i.operand],
+        Instruction::FPToUI(i) => vec![&i.operand],
+        Instruction::FPToSI(i) => vec![&i.operand],
+        Instruction::UIToFP(i) => vec![&i.operand],
+        Instruction::SIToFP(i) => vec![&i.operand],
+        Instruction::PtrToInt(i) => vec![&i.operand],
+        Instruction::IntToPtr(i) => vec![&i.operand],
+        Instruction::BitCast(i) => vec![&i.operand],
+        Instruction::AddrSpaceCast(i) => vec![&i.operand],
+        Instruction::ICmp(i) => vec![&i.operand0, &i.operand1],
+        Instruction::FCmp(i) => vec![&i.operand0, &i.operand1],
+        Instruction::Phi(i) => i.incoming_values.iter().map(|(value, _)| value).collect(),
+        Instruction::Select(i) => vec![&i.condition, &i.true_value, &i.false_value],
+        Instruction::Call(i) => i
+            .arguments
+            .iter()
+            .map(|(arg, _)| arg)
+            .chain(i.function.as_ref().right())
+            .collect(),
+        Instruction::VAArg(i) => vec![&i.arg_list],
+        Instruction::LandingPad(_) => vec![],
+        Instruction::CatchPad(i) => std::iter::once(&i.catch_switch)
+            .chain(i.args.iter())
+            .collect(),
+        Instruction::CleanupPad(i) => std::iter::once(&i.parent_pad)
+            .chain(i.args.iter())
+            .collect(),
+        Instruction::Freeze(i) => vec![&i.operand],
+    }
+}
+
+/// Every value operand of a terminator, successor labels excluded
+pub fn terminator_operands(terminator: &Terminator) -> Vec<&Operand> {
+    match terminator {
+        Terminator::Ret(t) => t.return_operand.iter().collect(),
+        Terminator::Br(_) => vec![],
+        Terminator::CondBr(t) => vec![&t.condition],
+        Terminator::Switch(t) => vec![&t.operand],
+        Terminator::IndirectBr(t) => vec![&t.operand],
+        Terminator::Invoke(t) => t
+            .arguments
+            .iter()
+            .map(|(arg, _)| arg)
+            .chain(t.function.as_ref().right())
+            .collect(),
+        Terminator::Resume(t) => vec![&t.operand],
+        Terminator::Unreachable(_) => vec![],
+        Terminator::CleanupRet(t) => vec![&t.cleanup_pad],
+        Terminator::CatchRet(t) => vec![&t.catch_pad],
+        Terminator::CatchSwitch(t) => vec![&t.parent_pad],
+        Terminator::CallBr(t) => t
+            .arguments
+            .iter()
+            .map(|(arg, _)| arg)
+            .chain(t.function.as_ref().right())
+            .collect(),
+    }
+}
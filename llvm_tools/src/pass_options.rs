@@ -0,0 +1,182 @@
+use llvm_sys::transforms::pass_builder::*;
+use pyo3::{pyclass, pymethods};
+
+#[pyclass]
+#[derive(Clone, Default)]
+/// Settings forwarded to `LLVMPassBuilderOptions`, `None` keeps LLVM's default
+pub struct PassOptions {
+    #[pyo3(get, set)]
+    /// Run the verifier after every pass
+    pub verify_each: Option<bool>,
+    #[pyo3(get, set)]
+    /// Print the passes as they run
+    pub debug_logging: Option<bool>,
+    #[pyo3(get, set)]
+    pub loop_interleaving: Option<bool>,
+    #[pyo3(get, set)]
+    pub loop_vectorization: Option<bool>,
+    #[pyo3(get, set)]
+    pub slp_vectorization: Option<bool>,
+    #[pyo3(get, set)]
+    pub loop_unrolling: Option<bool>,
+    #[pyo3(get, set)]
+    pub forget_all_scev_in_loop_unroll: Option<bool>,
+    #[pyo3(get, set)]
+    pub licm_mssa_opt_cap: Option<u32>,
+    #[pyo3(get, set)]
+    pub licm_mssa_no_acc_for_promotion_cap: Option<u32>,
+    #[pyo3(get, set)]
+    pub call_graph_profile: Option<bool>,
+    #[pyo3(get, set)]
+    pub merge_functions: Option<bool>,
+    #[pyo3(get, set)]
+    /// Inliner threshold used by the `default<O*>` pipelines
+    pub inliner_threshold: Option<i32>,
+    #[pyo3(get, set)]
+    /// Default threshold of every inline cost analysis, `cgscc(inline)` included, before
+    /// the hint, cold and call site adjustments (`-inline-threshold`)
+    pub inline_threshold: Option<i32>,
+    #[pyo3(get, set)]
+    /// Threshold for callees marked `inlinehint` (`-inlinehint-threshold`)
+    pub inline_hint_threshold: Option<i32>,
+    #[pyo3(get, set)]
+    /// Threshold for call sites profile data marks hot (`-hot-callsite-threshold`)
+    pub hot_callsite_threshold: Option<i32>,
+    #[pyo3(get, set)]
+    /// Threshold for call sites hot relative to their caller
+    /// (`-locally-hot-callsite-threshold`)
+    pub locally_hot_callsite_threshold: Option<i32>,
+    #[pyo3(get, set)]
+    /// Threshold for call sites profile data marks cold (`-inline-cold-callsite-threshold`)
+    pub cold_callsite_threshold: Option<i32>,
+}
+
+#[pymethods]
+impl PassOptions {
+    #[new]
+    #[pyo3(signature = (
+        verify_each = None,
+        debug_logging = None,
+        loop_interleaving = None,
+        loop_vectorization = None,
+        slp_vectorization = None,
+        loop_unrolling = None,
+        forget_all_scev_in_loop_unroll = None,
+        licm_mssa_opt_cap = None,
+        licm_mssa_no_acc_for_promotion_cap = None,
+        call_graph_profile = None,
+        merge_functions = None,
+        inliner_threshold = None,
+        inline_threshold = None,
+        inline_hint_threshold = None,
+        hot_callsite_threshold = None,
+        locally_hot_callsite_threshold = None,
+        cold_callsite_threshold = None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        verify_each: Option<bool>,
+        debug_logging: Option<bool>,
+        loop_interleaving: Option<bool>,
+        loop_vectorization: Option<bool>,
+        slp_vectorization: Option<bool>,
+        loop_unrolling: Option<bool>,
+        forget_all_scev_in_loop_unroll: Option<bool>,
+        licm_mssa_opt_cap: Option<u32>,
+        licm_mssa_no_acc_for_promotion_cap: Option<u32>,
+        call_graph_profile: Option<bool>,
+        merge_functions: Option<bool>,
+        inliner_threshold: Option<i32>,
+        inline_threshold: Option<i32>,
+        inline_hint_threshold: Option<i32>,
+        hot_callsite_threshold: Option<i32>,
+        locally_hot_callsite_threshold: Option<i32>,
+        cold_callsite_threshold: Option<i32>,
+    ) -> Self {
+        Self {
+            verify_each,
+            debug_logging,
+            loop_interleaving,
+            loop_vectorization,
+            slp_vectorization,
+            loop_unrolling,
+            forget_all_scev_in_loop_unroll,
+            licm_mssa_opt_cap,
+            licm_mssa_no_acc_for_promotion_cap,
+            call_graph_profile,
+            merge_functions,
+            inliner_threshold,
+            inline_threshold,
+            inline_hint_threshold,
+            hot_callsite_threshold,
+            locally_hot_callsite_threshold,
+            cold_callsite_threshold,
+        }
+    }
+}
+
+impl PassOptions {
+    /// Creates `LLVMPassBuilderOptions` with every option that is set applied,
+    /// the caller disposes of them
+    pub unsafe fn to_llvm(&self) -> LLVMPassBuilderOptionsRef {
+        let opts = LLVMCreatePassBuilderOptions();
+        if let Some(verify_each) = self.verify_each {
+            LLVMPassBuilderOptionsSetVerifyEach(opts, verify_each as i32);
+        }
+        if let Some(debug_logging) = self.debug_logging {
+            LLVMPassBuilderOptionsSetDebugLogging(opts, debug_logging as i32);
+        }
+        if let Some(loop_interleaving) = self.loop_interleaving {
+            LLVMPassBuilderOptionsSetLoopInterleaving(opts, loop_interleaving as i32);
+        }
+        if let Some(loop_vectorization) = self.loop_vectorization {
+            LLVMPassBuilderOptionsSetLoopVectorization(opts, loop_vectorization as i32);
+        }
+        if let Some(slp_vectorization) = self.slp_vectorization {
+            LLVMPassBuilderOptionsSetSLPVectorization(opts, slp_vectorization as i32);
+        }
+        if let Some(loop_unrolling) = self.loop_unrolling {
+            LLVMPassBuilderOptionsSetLoopUnrolling(opts, loop_unrolling as i32);
+        }
+        if let Some(forget_all_scev) = self.forget_all_scev_in_loop_unroll {
+            LLVMPassBuilderOptionsSetForgetAllSCEVInLoopUnroll(opts, forget_all_scev as i32);
+        }
+        if let Some(call_graph_profile) = self.call_graph_profile {
+            LLVMPassBuilderOptionsSetCallGraphProfile(opts, call_graph_profile as i32);
+        }
+        if let Some(merge_functions) = self.merge_functions {
+            LLVMPassBuilderOptionsSetMergeFunctions(opts, merge_functions as i32);
+        }
+        if let Some(cap) = self.licm_mssa_opt_cap {
+            LLVMPassBuilderOptionsSetLicmMssaOptCap(opts, cap);
+        }
+        if let Some(cap) = self.licm_mssa_no_acc_for_promotion_cap {
+            LLVMPassBuilderOptionsSetLicmMssaNoAccForPromotionCap(opts, cap);
+        }
+        if let Some(threshold) = self.inliner_threshold {
+            LLVMPassBuilderOptionsSetInlinerThreshold(opts, threshold);
+        }
+        opts
+    }
+
+    /// The LLVM command line options to set for the inline cost knobs the pass builder
+    /// does not expose, as (name, value)
+    pub fn command_line_options(&self) -> Vec<(&'static str, String)> {
+        [
+            ("inline-threshold", self.inline_threshold),
+            ("inlinehint-threshold", self.inline_hint_threshold),
+            ("hot-callsite-threshold", self.hot_callsite_threshold),
+            (
+                "locally-hot-callsite-threshold",
+                self.locally_hot_callsite_threshold,
+            ),
+            (
+                "inline-cold-callsite-threshold",
+                self.cold_callsite_threshold,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?.to_string())))
+        .collect()
+    }
+}
@@ -0,0 +1,518 @@
+use std::collections::HashMap;
+
+use llvm_ir::{types::Typed, Constant, Instruction, Module, Name, Operand, Terminator, Type};
+use numpy::PyArray2;
+use polars::io::ipc::IpcWriter;
+use polars::prelude::*;
+use pyo3::{prelude::*, pyclass, pymethods, types::PyBytes, Bound, PyResult};
+
+use crate::{
+    bb_features::{instruction_to_string, terminator_to_string},
+    call_site::{callee_name, global_name},
+    cfg::Cfg,
+    operands::{instruction_operands, terminator_operands},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum NodeKind {
+    Instruction,
+    Variable,
+    Constant,
+}
+
+impl NodeKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            NodeKind::Instruction => "instruction",
+            NodeKind::Variable => "variable",
+            NodeKind::Constant => "constant",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Flow {
+    Control,
+    Data,
+    Call,
+}
+
+impl Flow {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Flow::Control => "control",
+            Flow::Data => "data",
+            Flow::Call => "call",
+        }
+    }
+}
+
+struct Node {
+    kind: NodeKind,
+    text: String,
+    full_text: String,
+    function: Option<String>,
+    block: Option<String>,
+}
+
+struct Edge {
+    source: usize,
+    target: usize,
+    flow: Flow,
+    position: usize,
+}
+
+#[pyclass]
+/// A ProGraML-style program graph of a module. Instruction nodes are labelled with
+/// their opcode, variable and constant nodes with their type; node 0 is the
+/// `[external]` instruction standing in for code outside the module.
+///
+/// - control edges link consecutive instructions and terminators to successor blocks
+/// - data edges link operands to their users and instructions to their results
+/// - call edges link calls to the callee's entry and its returns back to the call
+pub struct ProgramGraph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+}
+
+#[pymethods]
+impl ProgramGraph {
+    #[new]
+    pub fn new(bc: Bound<PyBytes>) -> PyResult<Self> {
+        let module = Module::from_bc_bytes(bc.as_bytes())
+            .map_err(PyErr::new::<pyo3::exceptions::PyValueError, _>)?;
+        Ok(Self::from_module(&module))
+    }
+
+    #[getter]
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    #[getter]
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// The nodes as an Arrow IPC table: node_id, kind, text, full_text, function, block.
+    /// Ids are Int64, as in `edge_index`.
+    pub fn nodes(&self) -> PyResult<Vec<u8>> {
+        let df = df!(
+            "node_id" => (0..self.nodes.len() as i64).collect::<Vec<_>>(),
+            "kind" => self.nodes.iter().map(|node| node.kind.as_str()).collect::<Vec<_>>(),
+            "text" => self.nodes.iter().map(|node| node.text.as_str()).collect::<Vec<_>>(),
+            "full_text" => self.nodes.iter().map(|node| node.full_text.as_str()).collect::<Vec<_>>(),
+            "function" => self.nodes.iter().map(|node| node.function.as_deref()).collect::<Vec<_>>(),
+            "block" => self.nodes.iter().map(|node| node.block.as_deref()).collect::<Vec<_>>(),
+        );
+        to_ipc(df)
+    }
+
+    /// The edges as an Arrow IPC table: source, target, flow, position, all Int64 but
+    /// flow
+    pub fn edges(&self) -> PyResult<Vec<u8>> {
+        let df = df!(
+            "source" => self.edges.iter().map(|edge| edge.source as i64).collect::<Vec<_>>(),
+            "target" => self.edges.iter().map(|edge| edge.target as i64).collect::<Vec<_>>(),
+            "flow" => self.edges.iter().map(|edge| edge.flow.as_str()).collect::<Vec<_>>(),
+            "position" => self.edges.iter().map(|edge| edge.position as i64).collect::<Vec<_>>(),
+        );
+        to_ipc(df)
+    }
+
+    /// The edges as a `(2, num_edges)` int64 array of sources and targets, the
+    /// `edge_index` layout of PyTorch Geometric. `flow` keeps only control, data or
+    /// call edges.
+    #[pyo3(signature = (flow = None))]
+    pub fn edge_index<'py>(
+        &self,
+        py: Python<'py>,
+        flow: Option<&str>,
+    ) -> PyResult<Bound<'py, PyArray2<i64>>> {
+        if let Some(flow) = flow {
+            if !matches!(flow, "control" | "data" | "call") {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!(
+                    "unknown flow {}, expected one of control, data, call",
+                    flow
+                )));
+            }
+        }
+        let (sources, targets): (Vec<i64>, Vec<i64>) = self
+            .edges
+            .iter()
+            .filter(|edge| flow.is_none_or(|flow| edge.flow.as_str() == flow))
+            .map(|edge| (edge.source as i64, edge.target as i64))
+            .unzip();
+        PyArray2::from_vec2(py, &[sources, targets])
+            .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))
+    }
+}
+
+impl ProgramGraph {
+    fn from_module(module: &Module) -> Self {
+        let mut graph = Self {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        };
+        let root = graph.add_node(
+            NodeKind::Instruction,
+            "[external]",
+            "[external]",
+            None,
+            None,
+        );
+        // global name -> variable node, shared by every function
+        let mut globals: HashMap<String, usize> = HashMap::new();
+        // function name -> (entry instruction, return instructions)
+        let mut boundaries: HashMap<String, (usize, Vec<usize>)> = HashMap::new();
+        // call instruction -> name of the called function
+        let mut calls: Vec<(usize, Option<String>)> = Vec::new();
+
+        for function in module.functions.iter() {
+            let function_name = Some(function.name.to_string());
+            let cfg = Cfg::new(function);
+
+            // an instruction node for every instruction, terminators last
+            let mut instr_nodes: Vec<Vec<usize>> = Vec::with_capacity(cfg.block_count());
+            let mut returns = Vec::new();
+            for bb in function.basic_blocks.iter() {
+                let block = Some(bb.name.to_string());
+                let mut bb_nodes = Vec::with_capacity(bb.instrs.len() + 1);
+                for instr in bb.instrs.iter() {
+                    bb_nodes.push(graph.add_node(
+                        NodeKind::Instruction,
+                        instruction_to_string(instr),
+                        &instr.to_string(),
+                        function_name.clone(),
+                        block.clone(),
+                    ));
+                }
+                let term_node = graph.add_node(
+                    NodeKind::Instruction,
+                    terminator_to_string(&bb.term),
+                    &bb.term.to_string(),
+                    function_name.clone(),
+                    block.clone(),
+                );
+                if matches!(bb.term, Terminator::Ret(_)) {
+                    returns.push(term_node);
+                }
+                bb_nodes.push(term_node);
+                instr_nodes.push(bb_nodes);
+            }
+            for (bb_index, bb_nodes) in instr_nodes.iter().enumerate() {
+                for pair in bb_nodes.windows(2) {
+                    graph.add_edge(pair[0], pair[1], Flow::Control, 0);
+                }
+                let term_node = *bb_nodes.last().unwrap();
+                for (position, &succ) in cfg.succs[bb_index].iter().enumerate() {
+                    graph.add_edge(term_node, instr_nodes[succ][0], Flow::Control, position);
+                }
+            }
+            if let Some(entry) = instr_nodes.first() {
+                boundaries.insert(function.name.to_string(), (entry[0], returns));
+            }
+
+            // a variable node for every argument and instruction result
+            let mut values: HashMap<&Name, usize> = HashMap::new();
+            for param in function.parameters.iter() {
+                let node = graph.add_node(
+                    NodeKind::Variable,
+                    &param.ty.to_string(),
+                    &format!("{} {}", param.ty, param.name),
+                    function_name.clone(),
+                    None,
+                );
+                values.insert(&param.name, node);
+            }
+            for (bb, bb_nodes) in function.basic_blocks.iter().zip(instr_nodes.iter()) {
+                for (instr, &instr_node) in bb.instrs.iter().zip(bb_nodes.iter()) {
+                    if let Some(result) = instr.try_get_result() {
+                        let ty = instr.get_type(&module.types);
+                        let node = graph.add_node(
+                            NodeKind::Variable,
+                            &ty.to_string(),
+                            &format!("{} {}", ty, result),
+                            function_name.clone(),
+                            None,
+                        );
+                        graph.add_edge(instr_node, node, Flow::Data, 0);
+                        values.insert(result, node);
+                    }
+                }
+                if let Terminator::Invoke(invoke) = &bb.term {
+                    // invokes of void functions still carry a result name
+                    let ty = bb.term.get_type(&module.types);
+                    if !matches!(ty.as_ref(), Type::VoidType) {
+                        let node = graph.add_node(
+                            NodeKind::Variable,
+                            &ty.to_string(),
+                            &format!("{} {}", ty, invoke.result),
+                            function_name.clone(),
+                            None,
+                        );
+                        graph.add_edge(*bb_nodes.last().unwrap(), node, Flow::Data, 0);
+                        values.insert(&invoke.result, node);
+                    }
+                }
+            }
+
+            // data edges from every operand, constants deduplicated per function
+            let mut constants: HashMap<String, usize> = HashMap::new();
+            for (bb, bb_nodes) in function.basic_blocks.iter().zip(instr_nodes.iter()) {
+                let term_node = *bb_nodes.last().unwrap();
+                let mut users: Vec<(usize, Vec<&Operand>)> = bb
+                    .instrs
+                    .iter()
+                    .zip(bb_nodes.iter())
+                    .map(|(instr, &node)| (node, data_operands(instr)))
+                    .collect();
+                users.push((term_node, data_terminator_operands(&bb.term)));
+
+                for (user, operands) in users {
+                    for (position, operand) in operands.into_iter().enumerate() {
+                        let source = match operand {
+                            Operand::LocalOperand { name, .. } => values.get(name).copied(),
+                            Operand::ConstantOperand(constant) => match constant.as_ref() {
+                                Constant::GlobalReference { name, .. } => {
+                                    let name = global_name(name);
+                                    Some(*globals.entry(name.clone()).or_insert_with(|| {
+                                        graph.add_node(
+                                            NodeKind::Variable,
+                                            "ptr",
+                                            &format!("@{}", name),
+                                            None,
+                                            None,
+                                        )
+                                    }))
+                                }
+                                _ => {
+                                    let full_text = constant.to_string();
+                                    Some(*constants.entry(full_text.clone()).or_insert_with(|| {
+                                        graph.add_node(
+                                            NodeKind::Constant,
+                                            &constant.get_type(&module.types).to_string(),
+                                            &full_text,
+                                            function_name.clone(),
+                                            None,
+                                        )
+                                    }))
+                                }
+                            },
+                            Operand::MetadataOperand => None,
+                        };
+                        if let Some(source) = source {
+                            graph.add_edge(source, user, Flow::Data, position);
+                        }
+                    }
+                }
+
+                for (instr, &node) in bb.instrs.iter().zip(bb_nodes.iter()) {
+                    if let Instruction::Call(call) = instr {
+                        calls.push((node, callee_name(call.function.as_ref().right())));
+                    }
+                }
+                match &bb.term {
+                    Terminator::Invoke(invoke) => {
+                        calls.push((term_node, callee_name(invoke.function.as_ref().right())))
+                    }
+                    Terminator::CallBr(callbr) => {
+                        calls.push((term_node, callee_name(callbr.function.as_ref().right())))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // the external world may call any function, which returns to it
+        for function in module.functions.iter() {
+            let (entry, returns) = &boundaries[&function.name.to_string()];
+            graph.add_edge(root, *entry, Flow::Call, 0);
+            for &ret in returns {
+                graph.add_edge(ret, root, Flow::Call, 0);
+            }
+        }
+        for (call, callee) in calls {
+            match callee.and_then(|callee| boundaries.get(&callee)) {
+                Some((entry, returns)) => {
+                    graph.add_edge(call, *entry, Flow::Call, 0);
+                    for &ret in returns {
+                        graph.add_edge(ret, call, Flow::Call, 0);
+                    }
+                }
+                // declarations, intrinsics, indirect calls and inline asm
+                None => {
+                    graph.add_edge(call, root, Flow::Call, 0);
+                    graph.add_edge(root, call, Flow::Call, 0);
+                }
+            }
+        }
+        graph
+    }
+
+    fn add_node(
+        &mut self,
+        kind: NodeKind,
+        text: &str,
+        full_text: &str,
+        function: Option<String>,
+        block: Option<String>,
+    ) -> usize {
+        self.nodes.push(Node {
+            kind,
+            text: text.to_string(),
+            full_text: full_text.to_string(),
+            function,
+            block,
+        });
+        self.nodes.len() - 1
+    }
+
+    fn add_edge(&mut self, source: usize, target: usize, flow: Flow, position: usize) {
+        self.edges.push(Edge {
+            source,
+            target,
+            flow,
+            position,
+        });
+    }
+}
+
+// the operands flowing into an instruction; callees are linked by call edges instead
+fn data_operands(instruction: &Instruction) -> Vec<&Operand> {
+    match instruction {
+        Instruction::Call(call) => call.arguments.iter().map(|(arg, _)| arg).collect(),
+        _ => instruction_operands(instruction),
+    }
+}
+
+fn data_terminator_operands(terminator: &Terminator) -> Vec<&Operand> {
+    match terminator {
+        Terminator::Invoke(invoke) => invoke.arguments.iter().map(|(arg, _)| arg).collect(),
+        Terminator::CallBr(callbr) => callbr.arguments.iter().map(|(arg, _)| arg).collect(),
+        _ => terminator_operands(terminator),
+    }
+}
+
+fn to_ipc(df: PolarsResult<DataFrame>) -> PyResult<Vec<u8>> {
+    let mut df = df.map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    let mut buf = Vec::new();
+    IpcWriter::new(&mut buf)
+        .finish(&mut df)
+        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(e.to_string()))?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use polars::io::ipc::IpcReader;
+
+    use super::*;
+    use crate::test_utils::module;
+
+    const IR: &str = r#"
+@g = global i32 0
+
+define i32 @callee(i32 %x) {
+entry:
+  %y = add i32 %x, 1
+  ret i32 %y
+}
+
+define i32 @caller() {
+entry:
+  %v = load i32, ptr @g
+  %r = call i32 @callee(i32 %v)
+  %s = call i32 @ext()
+  ret i32 %r
+}
+
+declare i32 @ext()
+"#;
+
+    fn graph() -> ProgramGraph {
+        ProgramGraph::from_module(&module(IR))
+    }
+
+    fn read(ipc: Vec<u8>) -> DataFrame {
+        IpcReader::new(Cursor::new(ipc)).finish().unwrap()
+    }
+
+    fn node(graph: &ProgramGraph, function: &str, text: &str) -> usize {
+        graph
+            .nodes
+            .iter()
+            .position(|node| node.function.as_deref() == Some(function) && node.text == text)
+            .unwrap()
+    }
+
+    fn has_edge(graph: &ProgramGraph, source: usize, target: usize, flow: Flow) -> bool {
+        graph
+            .edges
+            .iter()
+            .any(|edge| edge.source == source && edge.target == target && edge.flow == flow)
+    }
+
+    #[test]
+    fn builds_nodes_and_edges_of_every_flow() {
+        let graph = graph();
+        // [external]; @callee: 2 instructions, %x, %y, 1; @caller: 4 instructions,
+        // %v, %r, %s; @g
+        assert_eq!(graph.node_count(), 14);
+        let count = |flow: Flow| graph.edges.iter().filter(|edge| edge.flow == flow).count();
+        assert_eq!(count(Flow::Control), 4);
+        assert_eq!(count(Flow::Data), 10);
+        assert_eq!(count(Flow::Call), 8);
+    }
+
+    #[test]
+    fn links_calls_to_callees_and_the_external_node() {
+        let graph = graph();
+        let entry = node(&graph, "callee", "add");
+        let ret = node(&graph, "callee", "ret");
+        let call = node(&graph, "caller", "call");
+        assert!(has_edge(&graph, call, entry, Flow::Call));
+        assert!(has_edge(&graph, ret, call, Flow::Call));
+        assert!(has_edge(&graph, 0, entry, Flow::Call));
+        assert!(has_edge(&graph, ret, 0, Flow::Call));
+        // @ext is only declared
+        let ext_call = call + 1;
+        assert!(has_edge(&graph, ext_call, 0, Flow::Call));
+        assert!(has_edge(&graph, 0, ext_call, Flow::Call));
+    }
+
+    #[test]
+    fn positions_data_edges_by_operand() {
+        let graph = graph();
+        let add = node(&graph, "callee", "add");
+        let positions: Vec<usize> = graph
+            .edges
+            .iter()
+            .filter(|edge| edge.target == add && edge.flow == Flow::Data)
+            .map(|edge| edge.position)
+            .collect();
+        assert_eq!(positions, [0, 1]);
+        let constant = graph
+            .nodes
+            .iter()
+            .position(|node| node.kind == NodeKind::Constant)
+            .unwrap();
+        assert_eq!(graph.nodes[constant].text, "i32");
+        assert!(has_edge(&graph, constant, add, Flow::Data));
+    }
+
+    #[test]
+    fn tables_use_int64_ids() {
+        let graph = graph();
+        let nodes = read(graph.nodes().unwrap());
+        assert_eq!(nodes.height(), 14);
+        assert_eq!(nodes.column("node_id").unwrap().dtype(), &DataType::Int64);
+        let edges = read(graph.edges().unwrap());
+        assert_eq!(edges.height(), 22);
+        for name in ["source", "target", "position"] {
+            assert_eq!(edges.column(name).unwrap().dtype(), &DataType::Int64);
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use pyo3::pyclass;
+
+#[pyclass]
+#[derive(Clone, Debug)]
+/// An optimization remark emitted by the inliner for one call
+pub struct InlineRemark {
+    #[pyo3(get)]
+    /// The remark name, such as Inlined, AlwaysInline, NotInlined, TooCostly, NeverInline
+    /// or IncreaseCostInOtherContexts
+    pub kind: String,
+    #[pyo3(get)]
+    /// The name of the calling function
+    pub caller: String,
+    #[pyo3(get)]
+    /// The name of the called function
+    pub callee: String,
+    #[pyo3(get)]
+    /// The inline cost, `None` for always/never inline decisions
+    pub cost: Option<i64>,
+    #[pyo3(get)]
+    /// The threshold the cost was compared against
+    pub threshold: Option<i64>,
+    #[pyo3(get)]
+    /// The reason LLVM attached to the decision, if any
+    pub reason: Option<String>,
+    #[pyo3(get)]
+    /// The full remark text, e.g. `'callee' inlined into 'caller' with (cost=5, threshold=225)`
+    pub message: String,
+}
+
+impl InlineRemark {
+    /// Builds the remark from its name and its arguments as the remark streamer records
+    /// them: `Callee`, `Caller`, `Cost`, `Threshold`, `Reason` and the text between them.
+    /// Returns `None` without a callee and caller.
+    pub fn new(kind: &str, args: &[(String, String)]) -> Option<Self> {
+        let arg = |key: &str| {
+            args.iter()
+                .find(|(arg_key, _)| arg_key == key)
+                .map(|(_, value)| value.as_str())
+        };
+        Some(Self {
+            kind: kind.to_string(),
+            caller: arg("Caller")?.to_string(),
+            callee: arg("Callee")?.to_string(),
+            cost: arg("Cost").and_then(|cost| cost.parse().ok()),
+            threshold: arg("Threshold").and_then(|threshold| threshold.parse().ok()),
+            reason: arg("Reason").map(str::to_string),
+            message: args.iter().map(|(_, value)| value.as_str()).collect(),
+        })
+    }
+
+    pub fn is_inlined(&self) -> bool {
+        self.kind == "Inlined" || self.kind == "AlwaysInline"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[(&str, &str)]) -> Vec<(String, String)> {
+        args.iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_cost_and_threshold_arguments() {
+        let remark = InlineRemark::new(
+            "TooCostly",
+            &args(&[
+                ("String", "'"),
+                ("Callee", "g"),
+                ("String", "' not inlined into '"),
+                ("Caller", "f"),
+                ("String", "' because too costly to inline (cost="),
+                ("Cost", "300"),
+                ("String", ", threshold="),
+                ("Threshold", "225"),
+                ("String", ")"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!((remark.caller.as_str(), remark.callee.as_str()), ("f", "g"));
+        assert_eq!((remark.cost, remark.threshold), (Some(300), Some(225)));
+        assert_eq!(remark.reason, None);
+        assert!(!remark.is_inlined());
+        assert_eq!(
+            remark.message,
+            "'g' not inlined into 'f' because too costly to inline (cost=300, threshold=225)"
+        );
+    }
+
+    #[test]
+    fn needs_a_caller_and_callee() {
+        assert!(InlineRemark::new("Inlined", &args(&[("Callee", "g")])).is_none());
+    }
+}
@@ -0,0 +1,42 @@
+use std::{
+    ffi::{CStr, CString},
+    ptr,
+};
+
+use llvm_ir::Module;
+use llvm_sys::{core::*, ir_reader::LLVMParseIRInContext};
+
+use crate::llvm_sys_wrapper::write_bitcode;
+
+/// Assembles textual IR into bitcode, panicking on invalid IR
+pub fn bitcode(ir: &str) -> Vec<u8> {
+    unsafe {
+        let ctx = LLVMContextCreate();
+        let buf_name = CString::new("test_ir").unwrap();
+        let mbuf = LLVMCreateMemoryBufferWithMemoryRangeCopy(
+            ir.as_ptr() as *const std::ffi::c_char,
+            ir.len(),
+            buf_name.as_ptr(),
+        );
+
+        // the parser takes ownership of the buffer
+        let mut module = ptr::null_mut();
+        let mut message = ptr::null_mut();
+        if LLVMParseIRInContext(ctx, mbuf, &mut module, &mut message) != 0 {
+            let error = CStr::from_ptr(message).to_string_lossy().into_owned();
+            LLVMDisposeMessage(message);
+            LLVMContextDispose(ctx);
+            panic!("invalid test IR: {}", error);
+        }
+
+        let bc = write_bitcode(module);
+        LLVMDisposeModule(module);
+        LLVMContextDispose(ctx);
+        bc
+    }
+}
+
+/// Parses textual IR the way `ModFeatures` parses bitcode
+pub fn module(ir: &str) -> Module {
+    Module::from_bc_bytes(&bitcode(ir)).expect("test IR converts to llvm-ir")
+}
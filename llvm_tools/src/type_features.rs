@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use llvm_ir::{
+    types::{FPType, Typed, Types},
+    BasicBlock, Type,
+};
+
+use crate::operands::{instruction_operands, terminator_operands};
+
+/// How many instruction results and operands of a block have each type, keyed by type
+/// as `type_key` names it
+pub fn type_histograms(
+    bb: &BasicBlock,
+    types: &Types,
+    include_terminator: bool,
+) -> (HashMap<String, usize>, HashMap<String, usize>) {
+    let mut result_types = HashMap::new();
+    let mut operand_types = HashMap::new();
+    for instr in bb.instrs.iter() {
+        count_type(&mut result_types, &instr.get_type(types));
+        for operand in instruction_operands(instr) {
+            count_type(&mut operand_types, &operand.get_type(types));
+        }
+    }
+    if include_terminator {
+        count_type(&mut result_types, &bb.term.get_type(types));
+        for operand in terminator_operands(&bb.term) {
+            count_type(&mut operand_types, &operand.get_type(types));
+        }
+    }
+    (result_types, operand_types)
+}
+
+fn count_type(histogram: &mut HashMap<String, usize>, ty: &Type) {
+    if let Some(key) = type_key(ty) {
+        *histogram.entry(key).or_insert(0) += 1;
+    }
+}
+
+/// The type of a value in LLVM syntax, every struct as `struct` and every array as
+/// `array`: `i32`, `double`, `<4 x float>`, `<vscale x 2 x i64>`, `ptr`,
+/// `ptr addrspace(1)`. `None` for void, labels, metadata and tokens.
+pub fn type_key(ty: &Type) -> Option<String> {
+    match ty {
+        Type::IntegerType { bits } => Some(format!("i{}", bits)),
+        Type::FPType(fp_type) => Some(
+            match fp_type {
+                FPType::Half => "half",
+                FPType::BFloat => "bfloat",
+                FPType::Single => "float",
+                FPType::Double => "double",
+                FPType::FP128 => "fp128",
+                FPType::X86_FP80 => "x86_fp80",
+                FPType::PPC_FP128 => "ppc_fp128",
+            }
+            .to_string(),
+        ),
+        Type::PointerType { addr_space } if *addr_space == 0 => Some("ptr".to_string()),
+        Type::PointerType { addr_space } => Some(format!("ptr addrspace({})", addr_space)),
+        Type::VectorType {
+            element_type,
+            num_elements,
+            scalable,
+        } => Some(format!(
+            "<{}{} x {}>",
+            if *scalable { "vscale x " } else { "" },
+            num_elements,
+            type_key(element_type)?
+        )),
+        Type::ArrayType { .. } => Some("array".to_string()),
+        Type::StructType { .. } | Type::NamedStructType { .. } => Some("struct".to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::module;
+
+    const IR: &str = r#"
+%pair = type { i32, i32 }
+
+define double @t(ptr %p, <4 x float> %v, ptr addrspace(1) %q) {
+entry:
+  %a = alloca i32
+  %x = load double, ptr %p
+  %e = extractelement <4 x float> %v, i32 0
+  %b = load i8, ptr addrspace(1) %q
+  %l = load [2 x i32], ptr %p
+  %s = insertvalue %pair undef, i32 1, 0
+  %sv = load <vscale x 2 x i64>, ptr %p
+  store i32 0, ptr %a
+  ret double %x
+}
+"#;
+
+    fn histograms(include_terminator: bool) -> (HashMap<String, usize>, HashMap<String, usize>) {
+        let module = module(IR);
+        let bb = &module.functions[0].basic_blocks[0];
+        type_histograms(bb, &module.types, include_terminator)
+    }
+
+    fn histogram(entries: &[(&str, usize)]) -> HashMap<String, usize> {
+        entries
+            .iter()
+            .map(|&(key, count)| (key.to_string(), count))
+            .collect()
+    }
+
+    #[test]
+    fn counts_result_types() {
+        // the store and the return have no result
+        let expected = histogram(&[
+            ("ptr", 1),
+            ("double", 1),
+            ("float", 1),
+            ("i8", 1),
+            ("array", 1),
+            ("struct", 1),
+            ("<vscale x 2 x i64>", 1),
+        ]);
+        assert_eq!(histograms(true).0, expected);
+        assert_eq!(histograms(false).0, expected);
+    }
+
+    #[test]
+    fn counts_operand_types() {
+        let (_, operand_types) = histograms(true);
+        assert_eq!(
+            operand_types,
+            histogram(&[
+                ("i32", 4),
+                ("ptr", 4),
+                ("<4 x float>", 1),
+                ("ptr addrspace(1)", 1),
+                ("struct", 1),
+                ("double", 1),
+            ])
+        );
+        // the returned double is the terminator's
+        let (_, operand_types) = histograms(false);
+        assert_eq!(operand_types.get("double"), None);
+        assert_eq!(operand_types["i32"], 4);
+    }
+}